@@ -0,0 +1,267 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use crate::strings::ui::*;
+use crate::{preconnect, socket_connect, ChatMessage, APP};
+use husky::{Client, Event, Events, UserKey};
+use std::{
+	env,
+	io::{self, Read},
+	path::PathBuf,
+	time::Duration,
+};
+
+/// Replay speeds that keep frame gaps sensible (and representable)
+const REPLAY_SPEEDS: std::ops::RangeInclusive<f64> = 0.01..=1000.0;
+
+#[derive(PartialEq, Debug)]
+pub enum Mode {
+	/// Regular interactive client
+	Tui,
+	/// Print usage and exit
+	Help,
+	/// Tie with `to`, send message (read from stdin if `None`) and untie
+	Send { to: String, message: Option<String> },
+	/// Tie with `from` and print every incoming message
+	Listen { from: String },
+	/// Bridge socket frames and JSON Lines on stdio
+	Jsonl,
+	/// Regular interactive client fed with frames recorded to `path` rather than a socket, `speed` times faster
+	Replay { path: PathBuf, speed: f64 },
+}
+
+/// Command line arguments are stored here
+#[derive(PartialEq, Debug)]
+pub struct Args {
+	pub mode: Mode,
+	pub key: Option<String>,
+	/// Seconds to wait for the subject to tie, and for the message to be delivered (`0` to wait forever)
+	pub timeout: u64,
+	/// File to record every protocol frame to
+	pub trace: Option<PathBuf>,
+}
+
+/// Parse command line arguments (without the executable name)
+pub fn parse_args(args: impl Iterator<Item = String>) -> Result<Args, String> {
+	let mut args = args.peekable();
+	let subcommand = match args.peek().map(|a| a.as_str()) {
+		Some("send") | Some("listen") => args.next(),
+		_ => None,
+	};
+	let mut to = None;
+	let mut from = None;
+	let mut message = None;
+	let mut key = None;
+	let mut timeout = 60;
+	let mut jsonl = false;
+	let mut trace = None;
+	let mut replay = None;
+	let mut speed = None;
+	while let Some(arg) = args.next() {
+		let mut value = || {
+			args.next()
+				.ok_or_else(|| format!("{} {}", CLI_MISSING_VALUE, arg))
+		};
+		match arg.as_str() {
+			"--help" | "-h" => {
+				return Ok(Args {
+					mode: Mode::Help,
+					key: None,
+					timeout,
+					trace: None,
+				})
+			}
+			"--to" => to = Some(value()?),
+			"--from" => from = Some(value()?),
+			"--message" | "-m" => message = Some(value()?),
+			"--key" => key = Some(value()?),
+			"--jsonl" => jsonl = true,
+			"--trace" => trace = Some(PathBuf::from(value()?)),
+			"--replay" => replay = Some(PathBuf::from(value()?)),
+			"--speed" => {
+				let v = value()?;
+				speed = match v.parse::<f64>() {
+					Ok(speed) if REPLAY_SPEEDS.contains(&speed) => Some(speed),
+					_ => return Err(format!("{} {}", CLI_BAD_VALUE, v)),
+				}
+			}
+			"--timeout" => {
+				let v = value()?;
+				timeout = v
+					.parse()
+					.map_err(|_| format!("{} {}", CLI_BAD_VALUE, v))?
+			}
+			_ => return Err(format!("{} {}", CLI_UNKNOWN_ARGUMENT, arg)),
+		}
+	}
+	let mode = match subcommand.as_deref() {
+		Some("send") => Mode::Send {
+			to: to.ok_or_else(|| format!("{} --to", CLI_MISSING_ARGUMENT))?,
+			message,
+		},
+		Some("listen") => Mode::Listen {
+			from: from.ok_or_else(|| format!("{} --from", CLI_MISSING_ARGUMENT))?,
+		},
+		Some(_) if jsonl => return Err(format!("{} --jsonl", CLI_UNKNOWN_ARGUMENT)),
+		Some(_) if replay.is_some() => return Err(format!("{} --replay", CLI_UNKNOWN_ARGUMENT)),
+		_ if jsonl && replay.is_some() => return Err(format!("{} --replay", CLI_UNKNOWN_ARGUMENT)),
+		_ if jsonl => Mode::Jsonl,
+		_ => match replay {
+			Some(path) => Mode::Replay {
+				path,
+				speed: speed.unwrap_or(1.0),
+			},
+			None => Mode::Tui,
+		},
+	};
+	if speed.is_some() && !matches!(mode, Mode::Replay { .. }) {
+		return Err(format!("{} --speed", CLI_UNKNOWN_ARGUMENT));
+	}
+	Ok(Args {
+		mode,
+		key,
+		timeout,
+		trace,
+	})
+}
+
+/// Perform headless command, without ever touching the terminal UI
+pub async unsafe fn run(args: Args) -> Result<(), String> {
+	let full_key = args
+		.key
+		.or_else(|| env::var("HUSKY_KEY").ok())
+		.ok_or_else(|| CLI_NO_KEY.to_string())?;
+	APP.user_key = Some(UserKey::new(full_key));
+	match args.mode {
+		Mode::Tui | Mode::Help | Mode::Replay { .. } => Ok(()),
+		Mode::Jsonl => crate::jsonl::run().await,
+		Mode::Send { to, message } => {
+			let message = match message {
+				Some(message) => message,
+				None => {
+					let mut message = String::new();
+					io::stdin()
+						.read_to_string(&mut message)
+						.map_err(|e| e.to_string())?;
+					message.trim_end().to_string()
+				}
+			};
+			if message.is_empty() {
+				return Err(CLI_EMPTY_MESSAGE.to_string());
+			}
+			let (client, mut events) = connect().await?;
+			tie(&client, &mut events, &to, args.timeout).await?;
+			deliver(&client, &mut events, &message, args.timeout).await?;
+			client.untie().ok();
+			client.drop_me().ok();
+			// Wait for the socket to be closed so that nothing is lost, but not for longer than anything else
+			let closed = async { while events.next().await.is_some() {} };
+			if args.timeout == 0 {
+				closed.await;
+			} else {
+				tokio::time::timeout(Duration::from_secs(args.timeout), closed).await.ok();
+			}
+			Ok(())
+		}
+		Mode::Listen { from } => {
+			let (client, mut events) = connect().await?;
+			tie(&client, &mut events, &from, args.timeout).await?;
+			loop {
+				match next_event(&mut events).await? {
+					Event::Message { sender, text, time, ttl } => {
+						let (rendered, altered) = ChatMessage::received(&sender, &text, time, ttl)
+							.render(false, &APP.config.time_format);
+						if altered {
+							println!("{} {}", rendered, MESSAGE_ALTERED);
+						} else {
+							println!("{}", rendered);
+						}
+					}
+					Event::Untie => return Err(TIE_BROKEN.to_string()),
+					_ => (),
+				}
+			}
+		}
+	}
+}
+
+/// Reach server, connect to socket and authorize
+async unsafe fn connect() -> Result<(Client, Events), String> {
+	preconnect().await.map_err(|e| e.to_string())?;
+	let (mut client, mut events) = socket_connect()
+		.await
+		.map_err(|_| AUTH_JOB_CONNECT_FAULT.to_string())?;
+	client
+		.auth(&APP.server.key, APP.user_key.as_ref().unwrap())
+		.map_err(|_| AUTH_JOB_CONNECT_FAULT.to_string())?;
+	loop {
+		match next_event(&mut events).await? {
+			Event::AuthOk => return Ok((client, events)),
+			Event::AuthFault => return Err(AUTH_JOB_CONNECT_AUTH_FAULT.to_string()),
+			Event::AuthFaultOverauth => return Err(AUTH_JOB_CONNECT_AUTH_FAULT_OVERAUTH.to_string()),
+			_ => (),
+		}
+	}
+}
+
+/// Tie with subject, waiting for them to tie back for `timeout` seconds (forever if `0`)
+async fn tie(client: &Client, events: &mut Events, subject: &str, timeout: u64) -> Result<(), String> {
+	client.tie(subject).map_err(|_| AUTH_JOB_CONNECT_FAULT.to_string())?;
+	let wait = async {
+		loop {
+			match next_event(events).await? {
+				Event::TieOk => return Ok(()),
+				Event::TieWait => eprintln!("{}", TIE_JOB_AWAITING),
+				Event::TieFaultNoUser => return Err(TIE_JOB_FAULT_NOUSER.to_string()),
+				Event::TieFaultSelfTie => return Err(TIE_JOB_FAULT_SELFTIE.to_string()),
+				Event::TieFaultOverTie => return Err(TIE_JOB_FAULT_OVERTIE.to_string()),
+				Event::Fault => return Err(RX_GENERAL_ERROR.to_string()),
+				_ => (),
+			}
+		}
+	};
+	if timeout == 0 {
+		return wait.await;
+	}
+	match tokio::time::timeout(Duration::from_secs(timeout), wait).await {
+		Ok(res) => res,
+		Err(_) => Err(CLI_TIE_TIMEOUT.to_string()),
+	}
+}
+
+/// Send message, waiting for the server to echo it back (which means it's delivered) for `timeout` seconds (forever if `0`)
+async fn deliver(client: &Client, events: &mut Events, message: &str, timeout: u64) -> Result<(), String> {
+	client.send(message).map_err(|_| AUTH_JOB_CONNECT_FAULT.to_string())?;
+	let wait = async {
+		loop {
+			match next_event(events).await? {
+				Event::Message { sender, text, .. } if Some(sender.as_str()) == client.username() && text == message => {
+					return Ok(())
+				}
+				Event::Untie => return Err(TIE_BROKEN.to_string()),
+				Event::Fault => return Err(RX_GENERAL_ERROR.to_string()),
+				_ => (),
+			}
+		}
+	};
+	if timeout == 0 {
+		return wait.await;
+	}
+	match tokio::time::timeout(Duration::from_secs(timeout), wait).await {
+		Ok(res) => res,
+		Err(_) => Err(CLI_DELIVERY_TIMEOUT.to_string()),
+	}
+}
+
+/// Wait for the next event, treating closed connection as an error
+async fn next_event(events: &mut Events) -> Result<Event, String> {
+	match events.next().await {
+		Some(Ok(event)) => Ok(event),
+		Some(Err(husky::Error::Send)) => Err(AUTH_JOB_CONNECT_FAULT.to_string()),
+		Some(Err(_)) => Err(MESSAGE_CORRUPTED_ERROR.to_string()),
+		None => Err(CONNECTION_DROPPED_ERROR.to_string()),
+	}
+}
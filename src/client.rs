@@ -0,0 +1,261 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use crate::flags::*;
+use crate::protocol::{self, Event, FileOffer, Status, UserKey};
+use crate::trace::{Direction, Trace};
+use chrono::Utc;
+use futures_util::{SinkExt, StreamExt};
+use std::{fmt, time::Duration};
+use tokio::sync::{mpsc, watch};
+use tokio_tungstenite::{connect_async, tungstenite::Message};
+
+/// Where the server lives
+#[derive(PartialEq, Clone, Debug)]
+pub struct Endpoint {
+	/// Root url where `preconnect.php` is located (w/o scheme, may include HTTP port)
+	pub root_url: String,
+	/// Port to connect to via websockets
+	pub port: String,
+}
+
+impl Endpoint {
+	pub fn new(root_url: &str, port: &str) -> Endpoint {
+		Endpoint {
+			root_url: root_url.to_string(),
+			port: port.to_string(),
+		}
+	}
+	fn preconnect_url(&self) -> String {
+		format!("http://{}/{}", self.root_url, "preconnect.php")
+	}
+	fn socket_url(&self) -> Result<url::Url, Error> {
+		let host = self
+			.root_url
+			.split([':', '/'])
+			.next()
+			.unwrap_or_default();
+		url::Url::parse(&format!("ws://{}:{}", host, self.port)).map_err(|_| Error::Connect)
+	}
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Error {
+	/// Unable to get response from `preconnect.php`
+	PreconnectUnreachable,
+	/// Unable to parse `preconnect.php` response
+	PreconnectUnparsable,
+	/// Server daemon isn't up or doesn't approve connections
+	PreconnectDisapproved,
+	/// Unable to connect to socket
+	Connect,
+	/// Unable to write to socket
+	Send,
+	/// Unable to read from socket
+	Corrupted,
+	/// Connection is already closed
+	Disconnected,
+}
+
+impl fmt::Display for Error {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(match self {
+			Error::PreconnectUnreachable => "unable to get response from server",
+			Error::PreconnectUnparsable => "unable to parse server response",
+			Error::PreconnectDisapproved => "connection not approved",
+			Error::Connect => "unable to connect to socket",
+			Error::Send => "unable to write to socket",
+			Error::Corrupted => "unable to read from socket",
+			Error::Disconnected => "connection closed",
+		})
+	}
+}
+
+impl std::error::Error for Error {}
+
+/// Handle to a live connection. Cheap to clone, every clone sends through the same socket
+#[derive(Clone)]
+pub struct Client {
+	outgoing: mpsc::UnboundedSender<String>,
+	username: Option<String>,
+	/// Never changes, only dropped once the socket is closed
+	closed: watch::Receiver<()>,
+}
+
+/// Stream of everything the server sends. Ends when connection is closed
+pub struct Events {
+	incoming: mpsc::UnboundedReceiver<Result<Event, Error>>,
+}
+
+impl Events {
+	/// Wait for the next event (`None` once connection is closed)
+	pub async fn next(&mut self) -> Option<Result<Event, Error>> {
+		self.incoming.recv().await
+	}
+}
+
+impl Client {
+	/// Make sure server daemon is up and approves connections
+	pub async fn preconnect(endpoint: &Endpoint) -> Result<(), Error> {
+		let res = reqwest::get(endpoint.preconnect_url())
+			.await
+			.map_err(|_| Error::PreconnectUnreachable)?;
+		let txt = res.text().await.map_err(|_| Error::PreconnectUnparsable)?;
+		if txt == "Ok" {
+			Ok(())
+		} else {
+			Err(Error::PreconnectDisapproved)
+		}
+	}
+	/// Open socket connection. Nothing is sent until asked to; it is closed once every [`Client`] clone is dropped
+	pub async fn connect(endpoint: &Endpoint) -> Result<(Client, Events), Error> {
+		Client::open(endpoint, None).await
+	}
+	/// Same as [`Client::connect`], recording every frame sent and received to `trace`
+	pub async fn connect_traced(endpoint: &Endpoint, trace: Trace) -> Result<(Client, Events), Error> {
+		Client::open(endpoint, Some(trace)).await
+	}
+	async fn open(endpoint: &Endpoint, trace: Option<Trace>) -> Result<(Client, Events), Error> {
+		let (socket, _) = connect_async(endpoint.socket_url()?)
+			.await
+			.map_err(|_| Error::Connect)?;
+		let (mut write, mut read) = socket.split();
+		let (outgoing, mut outgoing_rx) = mpsc::unbounded_channel::<String>();
+		let (incoming_tx, incoming) = mpsc::unbounded_channel();
+		let write_errors = incoming_tx.clone();
+		let write_trace = trace.clone();
+		let (closed_tx, closed) = watch::channel(());
+		tokio::spawn(async move {
+			while let Some(frame) = outgoing_rx.recv().await {
+				let dropme = frame.starts_with(TX_DROPME_FLAG);
+				if let Some(trace) = &write_trace {
+					trace.record(Direction::Tx, &frame);
+				}
+				if write.send(Message::Text(frame)).await.is_err() {
+					write_errors.send(Err(Error::Send)).ok();
+					break;
+				}
+				if dropme {
+					break;
+				}
+			}
+			// Either asked to, or every client handle is gone
+			write.close().await.ok();
+			drop(closed_tx);
+		});
+		tokio::spawn(async move {
+			while let Some(message) = read.next().await {
+				if let (Some(trace), Ok(Message::Text(txt))) = (&trace, &message) {
+					trace.record(Direction::Rx, txt);
+				}
+				let event = match message {
+					Ok(Message::Text(txt)) if !txt.is_empty() => Ok(Event::parse(&txt)),
+					Ok(Message::Close(_)) => break,
+					Ok(_) => continue,
+					Err(_) => Err(Error::Corrupted),
+				};
+				let failed = event.is_err();
+				if incoming_tx.send(event).is_err() || failed {
+					break;
+				}
+			}
+		});
+		Ok((
+			Client {
+				outgoing,
+				username: None,
+				closed,
+			},
+			Events { incoming },
+		))
+	}
+	/// Send raw frame
+	pub fn send_frame(&self, frame: String) -> Result<(), Error> {
+		self.outgoing.send(frame).map_err(|_| Error::Disconnected)
+	}
+	/// Authorize as a user. The result comes as [`Event::AuthOk`] or one of the auth faults
+	pub fn auth(&mut self, server_key: &str, user_key: &UserKey) -> Result<(), Error> {
+		self.username = Some(user_key.username.clone());
+		self.send_frame(format!("{}{}/{}", TX_AUTH_FLAG, server_key, user_key.full))
+	}
+	/// Name of the user this client authorized as
+	pub fn username(&self) -> Option<&str> {
+		self.username.as_deref()
+	}
+	/// Ask to tie with subject. The result comes as [`Event::TieOk`] (possibly after [`Event::TieWait`]) or one of the tie faults
+	pub fn tie(&self, subject: &str) -> Result<(), Error> {
+		self.send_frame(format!("{}{}", TX_TIE_INIT_FLAG, subject))
+	}
+	/// Break current tie (or stop waiting for one)
+	pub fn untie(&self) -> Result<(), Error> {
+		self.send_frame(RXTX_UNTIE_FLAG.to_string())
+	}
+	/// Send message to the tie subject. Server echoes it back as [`Event::Message`] once delivered
+	pub fn send(&self, text: &str) -> Result<(), Error> {
+		self.send_frame(protocol::message_encode(
+			self.username.as_deref().unwrap_or_default(),
+			text,
+			Utc::now(),
+			None,
+		))
+	}
+	/// Same as [`Client::send`], asking both ends to remove the message once `ttl` passes since it's sent
+	pub fn send_ephemeral(&self, text: &str, ttl: Duration) -> Result<(), Error> {
+		self.send_frame(protocol::message_encode(
+			self.username.as_deref().unwrap_or_default(),
+			text,
+			Utc::now(),
+			Some(ttl),
+		))
+	}
+	/// Let the tie subject know about user's status
+	pub fn set_status(&self, status: &Status) -> Result<(), Error> {
+		self.send_frame(status.encode())
+	}
+	/// Offer file to the tie subject. The answer comes as [`Event::FileAccept`] or [`Event::FileDecline`]
+	pub fn offer_file(&self, offer: &FileOffer) -> Result<(), Error> {
+		self.send_frame(offer.encode())
+	}
+	/// Accept file offered by the tie subject
+	pub fn accept_file(&self) -> Result<(), Error> {
+		self.send_frame(RXTX_FILE_ACCEPT_FLAG.to_string())
+	}
+	/// Decline file offered by the tie subject, or cancel the transfer in progress
+	pub fn decline_file(&self) -> Result<(), Error> {
+		self.send_frame(RXTX_FILE_DECLINE_FLAG.to_string())
+	}
+	/// Send next piece of the accepted file. The subject answers with [`Event::FileAck`] or, after the last one, [`Event::FileDone`]
+	pub fn send_file_chunk(&self, chunk: &[u8]) -> Result<(), Error> {
+		self.send_frame(format!("{}{}", RXTX_FILE_CHUNK_FLAG, base64::encode(chunk)))
+	}
+	/// Let the tie subject know the chunk is received
+	pub fn ack_file_chunk(&self) -> Result<(), Error> {
+		self.send_frame(RXTX_FILE_ACK_FLAG.to_string())
+	}
+	/// Let the tie subject know the whole file is received, and whether it's intact
+	pub fn file_done(&self, verified: bool) -> Result<(), Error> {
+		self.send_frame(format!(
+			"{}{}",
+			RXTX_FILE_DONE_FLAG,
+			if verified {
+				FILE_VERIFIED_ATTR
+			} else {
+				FILE_CORRUPTED_ATTR
+			}
+		))
+	}
+	/// Ask server to forget about this connection and close it
+	pub fn drop_me(&self) -> Result<(), Error> {
+		self.send_frame(TX_DROPME_FLAG.to_string())
+	}
+	/// Same as [`Client::drop_me`], waiting until the frame is sent and the socket is closed
+	pub async fn close(&self) {
+		// Socket may well be closed already, which is just as good
+		self.drop_me().ok();
+		let mut closed = self.closed.clone();
+		while closed.changed().await.is_ok() {}
+	}
+}
@@ -0,0 +1,203 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use crate::export::ExportFormat;
+use std::time::Duration;
+
+/// Every input starting with this is treated as a command
+pub const COMMAND_PREFIX: char = '/';
+
+/// All known commands along with their argument hints
+pub const COMMANDS: [(&str, &str); 12] = [
+	("/tie", " <user>"),
+	("/untie", ""),
+	("/logout", ""),
+	("/quit", ""),
+	("/clear", ""),
+	("/export", " [markdown|jsonl]"),
+	("/status", " [message]"),
+	("/file", " <path>"),
+	("/accept", ""),
+	("/decline", ""),
+	("/ttl", " <seconds|off>"),
+	("/help", ""),
+];
+
+const EXPORT_FORMATS: [&str; 2] = ["markdown", "jsonl"];
+
+#[derive(PartialEq, Debug)]
+pub enum Command {
+	Tie(String),
+	Untie,
+	Logout,
+	Quit,
+	Clear,
+	Export(Option<ExportFormat>),
+	Status(String),
+	File(String),
+	Accept,
+	Decline,
+	/// Lifetime of messages sent from now on (`None` to keep them)
+	Ttl(Option<Duration>),
+	Help,
+}
+
+#[derive(PartialEq, Debug)]
+pub enum CommandError {
+	Unknown(String),
+	MissingArgument(&'static str),
+	BadArgument(String),
+}
+
+/// Parse message input. Returns `None` if it's a regular message rather than a command
+/// (messages which need to start with a slash are escaped by doubling it)
+pub fn parse(input: &str) -> Option<Result<Command, CommandError>> {
+	let input = input.trim();
+	if !input.starts_with(COMMAND_PREFIX) || input.starts_with("//") {
+		return None;
+	}
+	let (name, arg) = match input.split_once(' ') {
+		Some((name, arg)) => (name, arg.trim()),
+		None => (input, ""),
+	};
+	Some(match name {
+		"/tie" if arg.is_empty() => Err(CommandError::MissingArgument(COMMANDS[0].1)),
+		"/tie" => Ok(Command::Tie(arg.to_string())),
+		"/untie" => Ok(Command::Untie),
+		"/logout" => Ok(Command::Logout),
+		"/quit" => Ok(Command::Quit),
+		"/clear" => Ok(Command::Clear),
+		"/export" => match arg {
+			"" => Ok(Command::Export(None)),
+			"markdown" | "md" => Ok(Command::Export(Some(ExportFormat::Markdown))),
+			"jsonl" => Ok(Command::Export(Some(ExportFormat::Jsonl))),
+			_ => Err(CommandError::BadArgument(arg.to_string())),
+		},
+		"/status" => Ok(Command::Status(arg.to_string())),
+		"/file" if arg.is_empty() => Err(CommandError::MissingArgument(COMMANDS[7].1)),
+		"/file" => Ok(Command::File(arg.to_string())),
+		"/accept" => Ok(Command::Accept),
+		"/decline" => Ok(Command::Decline),
+		"/ttl" if arg.is_empty() => Err(CommandError::MissingArgument(COMMANDS[10].1)),
+		"/ttl" => match arg.parse::<u64>() {
+			Ok(0) => Ok(Command::Ttl(None)),
+			Ok(secs) => Ok(Command::Ttl(Some(Duration::from_secs(secs)))),
+			Err(_) if arg == "off" => Ok(Command::Ttl(None)),
+			Err(_) => Err(CommandError::BadArgument(arg.to_string())),
+		},
+		"/help" => Ok(Command::Help),
+		_ => Err(CommandError::Unknown(name.to_string())),
+	})
+}
+
+/// Get message as it should be sent (undoing slash escaping)
+pub fn unescape(input: &str) -> &str {
+	if input.starts_with("//") {
+		&input[1..]
+	} else {
+		input
+	}
+}
+
+/// Complete command (or its argument) as far as it's unambiguous
+pub fn complete(input: &str) -> Option<String> {
+	if !input.starts_with(COMMAND_PREFIX) {
+		return None;
+	}
+	let (candidates, prefix, typed): (Vec<&str>, &str, &str) = match input.split_once(' ') {
+		Some(("/export", arg)) => (EXPORT_FORMATS.to_vec(), "/export ", arg),
+		Some(_) => return None,
+		None => (COMMANDS.iter().map(|(name, _)| *name).collect(), "", input),
+	};
+	let matching: Vec<&str> = candidates
+		.into_iter()
+		.filter(|c| c.starts_with(typed))
+		.collect();
+	match matching.as_slice() {
+		[] => None,
+		[only] => {
+			let takes_argument = COMMANDS
+				.iter()
+				.any(|(name, hint)| name == only && !hint.is_empty());
+			Some(format!(
+				"{}{}{}",
+				prefix,
+				only,
+				if takes_argument { " " } else { "" }
+			))
+		}
+		[first, rest @ ..] => {
+			let mut common = first.len();
+			for other in rest {
+				common = first
+					.chars()
+					.zip(other.chars())
+					.take_while(|(a, b)| a == b)
+					.count()
+					.min(common);
+			}
+			Some(format!("{}{}", prefix, &first[..common]))
+		}
+	}
+}
+
+/// Get a one-line list of all commands
+pub fn help() -> String {
+	COMMANDS
+		.iter()
+		.map(|(name, hint)| format!("{}{}", name, hint))
+		.collect::<Vec<String>>()
+		.join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn messages_are_not_commands() {
+		assert_eq!(parse("Hi"), None);
+		assert_eq!(parse("//tie is a command"), None);
+		assert_eq!(unescape("//tie is a command"), "/tie is a command");
+		assert_eq!(unescape("/ not escaped"), "/ not escaped");
+	}
+
+	#[test]
+	fn commands_are_parsed() {
+		assert_eq!(parse("  /untie "), Some(Ok(Command::Untie)));
+		assert_eq!(parse("/tie   bob"), Some(Ok(Command::Tie("bob".to_string()))));
+		assert_eq!(parse("/status Out for lunch"), Some(Ok(Command::Status("Out for lunch".to_string()))));
+		assert_eq!(parse("/file ~/My Documents/cv.pdf"), Some(Ok(Command::File("~/My Documents/cv.pdf".to_string()))));
+		assert_eq!(parse("/export md"), Some(Ok(Command::Export(Some(ExportFormat::Markdown)))));
+		assert_eq!(parse("/ttl 30"), Some(Ok(Command::Ttl(Some(Duration::from_secs(30))))));
+		assert_eq!(parse("/ttl off"), Some(Ok(Command::Ttl(None))));
+	}
+
+	#[test]
+	fn bad_commands_fault() {
+		assert_eq!(parse("/tia bob"), Some(Err(CommandError::Unknown("/tia".to_string()))));
+		assert_eq!(parse("/tie"), Some(Err(CommandError::MissingArgument(" <user>"))));
+		assert_eq!(parse("/file "), Some(Err(CommandError::MissingArgument(" <path>"))));
+		assert_eq!(parse("/ttl"), Some(Err(CommandError::MissingArgument(" <seconds|off>"))));
+		assert_eq!(parse("/export pdf"), Some(Err(CommandError::BadArgument("pdf".to_string()))));
+		assert_eq!(parse("/ttl soon"), Some(Err(CommandError::BadArgument("soon".to_string()))));
+	}
+
+	#[test]
+	fn commands_are_completed() {
+		assert_eq!(complete("/u"), Some("/untie".to_string()));
+		assert_eq!(complete("/sta"), Some("/status ".to_string()));
+		// Ambiguous prefixes are completed as far as they go
+		assert_eq!(complete("/t"), Some("/t".to_string()));
+		assert_eq!(complete("/ti"), Some("/tie ".to_string()));
+		assert_eq!(complete("/"), Some("/".to_string()));
+		assert_eq!(complete("/export j"), Some("/export jsonl".to_string()));
+		assert_eq!(complete("/export "), Some("/export ".to_string()));
+		assert_eq!(complete("/x"), None);
+		assert_eq!(complete("/tie b"), None);
+		assert_eq!(complete("Hi"), None);
+	}
+}
@@ -0,0 +1,187 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use crate::export::ExportFormat;
+use crate::strings::ui::*;
+use crate::keymap::{Action, Key};
+use crate::notify::NotifyMethod;
+use crate::theme::ThemeName;
+use chrono::format::{Item, StrftimeItems};
+use serde::Deserialize;
+use std::{collections::BTreeMap, env, fs, path::PathBuf, time::Duration};
+
+/// Name of the config file inside of the config directory
+const CONFIG_FILE_NAME: &str = "config.toml";
+
+/// User-tunable client settings, loaded from `config.toml`
+#[derive(Deserialize, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+	/// Seconds without key events after which the user is marked away (`0` to never)
+	pub idle_timeout: u64,
+	/// Custom status message to start every session with
+	pub status_message: String,
+	/// Whether to keep encrypted per-contact message history on disk
+	pub history: bool,
+	/// Days to keep history messages for (`0` to keep them forever)
+	pub history_retention_days: u64,
+	/// Passphrase to encrypt history with, unless `$HUSKY_HISTORY_PASSPHRASE` is set (history isn't kept without either)
+	pub history_passphrase: Option<String>,
+	/// Format of exported conversation transcripts
+	pub export_format: ExportFormat,
+	/// Directory to save exported transcripts and job logs to (current directory if not set)
+	pub export_dir: Option<PathBuf>,
+	/// Directory to save received files to (`~/Downloads` if not set)
+	pub download_dir: Option<PathBuf>,
+	/// Largest file (in MiB) to send or receive, as transfers are kept in memory (`0` for no limit)
+	pub max_file_size_mib: u64,
+	/// Seconds a job (authorizing, tying) may wait for the server before it fails, not counting the wait for the subject (`0` to wait forever)
+	pub job_timeout: u64,
+	/// Palette to draw the interface with (monochrome regardless if `$NO_COLOR` is set)
+	pub theme: ThemeName,
+	/// Keys bound to actions, replacing default bindings of these actions
+	pub keys: BTreeMap<Action, Vec<Key>>,
+	/// Language to show the interface in (taken from the locale if not set)
+	pub language: Option<String>,
+	/// Format of message timestamps (see `chrono::format::strftime`)
+	pub time_format: String,
+	/// How to notify about messages arriving while the terminal is unfocused
+	pub notify: NotifyMethod,
+	/// Shell command to run on every message arriving while the terminal is unfocused
+	pub notify_command: Option<String>,
+}
+
+impl Default for Config {
+	fn default() -> Config {
+		Config {
+			idle_timeout: 300,
+			status_message: String::new(),
+			history: false,
+			history_retention_days: 7,
+			history_passphrase: None,
+			export_format: ExportFormat::Markdown,
+			export_dir: None,
+			download_dir: None,
+			max_file_size_mib: 64,
+			job_timeout: 60,
+			theme: ThemeName::Default,
+			keys: BTreeMap::new(),
+			language: None,
+			time_format: "%H:%M".to_string(),
+			notify: NotifyMethod::Bell,
+			notify_command: None,
+		}
+	}
+}
+impl Config {
+	/// Get nullable const-friendly Config instance
+	pub const fn null() -> Config {
+		Config {
+			idle_timeout: 0,
+			status_message: String::new(),
+			history: false,
+			history_retention_days: 0,
+			history_passphrase: None,
+			export_format: ExportFormat::Markdown,
+			export_dir: None,
+			download_dir: None,
+			max_file_size_mib: 0,
+			job_timeout: 0,
+			theme: ThemeName::Default,
+			keys: BTreeMap::new(),
+			language: None,
+			time_format: String::new(),
+			notify: NotifyMethod::Off,
+			notify_command: None,
+		}
+	}
+	/// Directory where Husky keeps its config (`$HUSKY_CONFIG_DIR`, `$XDG_CONFIG_HOME/husky` or `~/.config/husky`)
+	pub fn dir() -> Option<PathBuf> {
+		if let Ok(dir) = env::var("HUSKY_CONFIG_DIR") {
+			return Some(PathBuf::from(dir));
+		}
+		if let Ok(dir) = env::var("XDG_CONFIG_HOME") {
+			return Some(PathBuf::from(dir).join("husky"));
+		}
+		env::var("HOME")
+			.ok()
+			.map(|home| PathBuf::from(home).join(".config").join("husky"))
+	}
+	/// Directory where Husky keeps its data (`$XDG_DATA_HOME/husky` or `~/.local/share/husky`)
+	pub fn data_dir() -> Option<PathBuf> {
+		if let Ok(dir) = env::var("XDG_DATA_HOME") {
+			return Some(PathBuf::from(dir).join("husky"));
+		}
+		env::var("HOME").ok().map(|home| {
+			PathBuf::from(home)
+				.join(".local")
+				.join("share")
+				.join("husky")
+		})
+	}
+	/// Directory where encrypted message history is stored
+	pub fn history_dir() -> Option<PathBuf> {
+		Config::data_dir().map(|dir| dir.join("history"))
+	}
+	/// Directory to save received files to
+	pub fn download_dir(&self) -> PathBuf {
+		if let Some(dir) = &self.download_dir {
+			return dir.clone();
+		}
+		env::var("HOME")
+			.map(|home| PathBuf::from(home).join("Downloads"))
+			.unwrap_or_else(|_| PathBuf::from("."))
+	}
+	/// Largest file to send or receive in bytes (`None` if there's no limit)
+	pub fn max_file_size(&self) -> Option<u64> {
+		match self.max_file_size_mib {
+			0 => None,
+			mib => mib.checked_mul(1024 * 1024),
+		}
+	}
+	/// Passphrase to encrypt history with (`None` if there's none, as the auth key is never reused for that)
+	pub fn history_passphrase(&self) -> Option<String> {
+		env::var("HUSKY_HISTORY_PASSPHRASE")
+			.ok()
+			.or_else(|| self.history_passphrase.clone())
+			.filter(|passphrase| !passphrase.is_empty())
+	}
+	/// How long history messages are kept for (`None` if forever, including periods too long to count)
+	pub fn history_retention(&self) -> Option<Duration> {
+		match self.history_retention_days {
+			0 => None,
+			days => days.checked_mul(24 * 60 * 60).map(Duration::from_secs),
+		}
+	}
+	/// How long a job may take (`None` if forever)
+	pub fn job_timeout(&self) -> Option<Duration> {
+		match self.job_timeout {
+			0 => None,
+			secs => Some(Duration::from_secs(secs)),
+		}
+	}
+	/// Check settings that can't be checked while parsing
+	fn validate(self) -> Result<Config, String> {
+		if StrftimeItems::new(&self.time_format).any(|item| item == Item::Error) {
+			return Err(format!("{} '{}'", CONFIG_TIME_FORMAT_FAULT, self.time_format));
+		}
+		Ok(self)
+	}
+	/// Read config from disk, falling back to defaults if there is no config file
+	pub fn load() -> Result<Config, String> {
+		let path = match Config::dir() {
+			Some(dir) => dir.join(CONFIG_FILE_NAME),
+			None => return Ok(Config::default()),
+		};
+		match fs::read_to_string(&path) {
+			Ok(txt) => toml::from_str::<Config>(&txt)
+				.map_err(|e| e.to_string())
+				.and_then(Config::validate)
+				.map_err(|e| format!("{}: {}", path.display(), e)),
+			Err(_) => Ok(Config::default()),
+		}
+	}
+}
@@ -0,0 +1,44 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use crate::config::Config;
+use chrono::{DateTime, Local};
+use std::{backtrace::Backtrace, env, fmt::Display, fs, io, path::PathBuf};
+
+/// Write report on `panic` to the data directory (or the temporary one if there's none), returning its path.
+/// Nothing the user typed or received makes it into the report
+pub fn report(panic: &dyn Display) -> io::Result<PathBuf> {
+	let now = Local::now();
+	let dir = Config::data_dir().unwrap_or_else(env::temp_dir);
+	fs::create_dir_all(&dir)?;
+	let path = dir.join(format!("crash-{}.txt", now.format("%Y%m%d-%H%M%S")));
+	fs::write(&path, render(panic, &Backtrace::force_capture(), now))?;
+	Ok(path)
+}
+
+fn render(panic: &dyn Display, backtrace: &dyn Display, time: DateTime<Local>) -> String {
+	format!(
+		"Husky v{} ({} {})\n{}\n\n{}\n\nBacktrace:\n{}\n",
+		env!("CARGO_PKG_VERSION"),
+		env::consts::OS,
+		env::consts::ARCH,
+		time.to_rfc3339(),
+		panic,
+		backtrace
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn report_tells_what_and_where() {
+		let report = render(&"panicked at src/ui.rs:1:1:\noops", &"0: husky::ui::draw", Local::now());
+		assert!(report.starts_with(&format!("Husky v{} (", env!("CARGO_PKG_VERSION"))));
+		assert!(report.contains("\n\npanicked at src/ui.rs:1:1:\noops\n\nBacktrace:\n0: husky::ui::draw\n"));
+	}
+}
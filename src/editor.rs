@@ -0,0 +1,99 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::{
+	env, fs,
+	io::{self, Write},
+	path::Path,
+	process::{self, Command},
+};
+
+/// Editor to fall back to if neither `$VISUAL` nor `$EDITOR` is set
+const DEFAULT_EDITOR: &str = if cfg!(windows) { "notepad" } else { "vi" };
+
+/// Get the user's editor command (`$VISUAL`, `$EDITOR` or `vi`)
+pub fn command() -> String {
+	["VISUAL", "EDITOR"]
+		.iter()
+		.filter_map(|var| env::var(var).ok())
+		.find(|editor| !editor.trim().is_empty())
+		.unwrap_or_else(|| DEFAULT_EDITOR.to_string())
+}
+
+/// Let the user write message in `editor`, starting with `draft`.
+/// The draft is kept in a temporary file which is wiped afterwards
+pub fn compose(editor: &str, draft: &str) -> io::Result<String> {
+	let path = env::temp_dir().join(format!("husky-{}-{:08x}.txt", process::id(), rand::random::<u32>()));
+	let mut options = fs::OpenOptions::new();
+	options.write(true).create_new(true);
+	// Nobody else has any business reading it
+	#[cfg(unix)]
+	options.mode(0o600);
+	options.open(&path)?.write_all(draft.as_bytes())?;
+	let composed = edit(editor, &path);
+	wipe(&path);
+	composed
+}
+
+fn edit(editor: &str, path: &Path) -> io::Result<String> {
+	// Editors are often set along with arguments, like `code --wait`
+	let mut words = editor.split_whitespace();
+	let program = words.next().unwrap_or(DEFAULT_EDITOR);
+	let status = Command::new(program).args(words).arg(path).status()?;
+	if !status.success() {
+		return Err(io::Error::other(format!("{} exited with {}", program, status)));
+	}
+	Ok(join(&fs::read_to_string(path)?))
+}
+
+/// Join lines with spaces, as messages are single-line
+fn join(text: &str) -> String {
+	text.lines()
+		.map(str::trim_end)
+		.filter(|line| !line.is_empty())
+		.collect::<Vec<&str>>()
+		.join(" ")
+}
+
+/// Overwrite file before removing it, so that the draft doesn't linger on disk
+fn wipe(path: &Path) {
+	if let Ok(metadata) = fs::metadata(path) {
+		fs::write(path, vec![0; metadata.len() as usize]).ok();
+	}
+	fs::remove_file(path).ok();
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn lines_are_joined() {
+		assert_eq!(join("Dear bob,\n\nthe build is\nbroken again.  \n"), "Dear bob, the build is broken again.");
+		assert_eq!(join("\n\n"), "");
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn draft_is_edited_and_wiped() {
+		// Plain POSIX tools only, as `sed -i` differs between GNU and BSD
+		let script = env::temp_dir().join(format!("husky-editor-test-{}.sh", process::id()));
+		fs::write(&script, "sed s/draft/final/ \"$1\" > \"$1.new\" && mv \"$1.new\" \"$1\"\n").unwrap();
+		let editor = format!("sh {}", script.display());
+		assert_eq!(compose(&editor, "A draft\nmessage\n").unwrap(), "A final message");
+		fs::remove_file(&script).ok();
+		let prefix = format!("husky-{}-", process::id());
+		let leftovers = fs::read_dir(env::temp_dir())
+			.unwrap()
+			.filter_map(Result::ok)
+			.filter(|entry| entry.file_name().to_string_lossy().starts_with(&prefix))
+			.count();
+		assert_eq!(leftovers, 0);
+		assert!(compose("false", "A draft").is_err());
+	}
+}
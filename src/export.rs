@@ -0,0 +1,121 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use crate::{ChatMessage, MessageState};
+use chrono::Local;
+use serde::Deserialize;
+use std::{
+	fs, io,
+	path::{Path, PathBuf},
+};
+
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+	Markdown,
+	Jsonl,
+}
+
+impl ExportFormat {
+	fn extension(&self) -> &'static str {
+		match self {
+			ExportFormat::Markdown => "md",
+			ExportFormat::Jsonl => "jsonl",
+		}
+	}
+}
+
+/// Write messages (newest first, as they are kept in chat) to a new transcript file in `dir`, leaving ephemeral ones out
+pub fn export(
+	dir: &Path,
+	username: &str,
+	subject: &str,
+	messages: &[ChatMessage],
+	format: ExportFormat,
+) -> io::Result<PathBuf> {
+	let now = Local::now();
+	let path = dir.join(format!(
+		"husky-{}-{}.{}",
+		file_safe(subject),
+		now.format("%Y%m%d-%H%M%S"),
+		format.extension()
+	));
+	let mut out = String::new();
+	match format {
+		ExportFormat::Markdown => {
+			out.push_str(&format!(
+				"# Husky transcript: {} & {}\n\n",
+				markdown_escape(username),
+				markdown_escape(subject)
+			));
+			out.push_str(&format!("_Exported {}_\n\n", now.format("%Y-%m-%d %H:%M:%S %:z")));
+			for m in messages.iter().rev().filter(|m| m.expires.is_none()) {
+				out.push_str(&format!(
+					"- `{}` **{}** _({})_: {}\n",
+					m.time.with_timezone(&Local).format("%Y-%m-%d %H:%M:%S"),
+					markdown_escape(&m.sender),
+					match m.state {
+						MessageState::Received => "received",
+						MessageState::Delivered => "delivered",
+						MessageState::Pending => "pending",
+					},
+					markdown_escape(&m.text)
+				));
+			}
+		}
+		ExportFormat::Jsonl => {
+			for m in messages.iter().rev().filter(|m| m.expires.is_none()) {
+				out.push_str(&serde_json::to_string(m)?);
+				out.push('\n');
+			}
+		}
+	}
+	fs::create_dir_all(dir)?;
+	fs::write(&path, out)?;
+	Ok(path)
+}
+
+/// Replace everything but ASCII letters, digits, `_` and `-`, so that the name can't lead out of the directory
+fn file_safe(name: &str) -> String {
+	name.chars()
+		.map(|c| if c.is_ascii_alphanumeric() || c == '_' || c == '-' { c } else { '_' })
+		.collect()
+}
+
+/// Escape text so that it can't make up any Markdown markup (headings, links, emphasis and such)
+fn markdown_escape(text: &str) -> String {
+	let mut escaped = String::with_capacity(text.len());
+	for c in text.chars() {
+		match c {
+			'\\' | '`' | '*' | '_' | '{' | '}' | '[' | ']' | '(' | ')' | '<' | '>' | '#' | '+' | '-' | '.' | '!'
+			| '|' | '~' => {
+				escaped.push('\\');
+				escaped.push(c);
+			}
+			'\n' | '\r' => escaped.push(' '),
+			_ => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn file_name_stays_in_dir() {
+		assert_eq!(file_safe("../../etc/passwd"), "______etc_passwd");
+		assert_eq!(file_safe("bob_the-builder42"), "bob_the-builder42");
+	}
+
+	#[test]
+	fn markdown_is_escaped() {
+		assert_eq!(markdown_escape("# [pwned](http://evil)"), "\\# \\[pwned\\]\\(http://evil\\)");
+		assert_eq!(markdown_escape("line\n## two"), "line \\#\\# two");
+		assert_eq!(markdown_escape("Hi, Bob"), "Hi, Bob");
+	}
+}
@@ -0,0 +1,206 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use crate::strings::ui::*;
+use crate::{App, AppState, Chat, ChatState, APP};
+use husky::{Download, FileOffer, Upload};
+use std::{env, fs, path::PathBuf};
+
+/// File transfer with the tie subject is stored here
+#[derive(PartialEq, Clone)]
+pub enum Transfer {
+	/// Subject offered a file, waiting for the user to accept or decline it
+	Offered(FileOffer),
+	/// User offered a file, waiting for the subject to accept or decline it
+	Offering(Upload),
+	Sending(Upload),
+	Receiving(Download),
+}
+
+impl Transfer {
+	/// Get title and percentage to show in progress gauge (if transfer is underway)
+	pub fn progress(&self) -> Option<(String, u16)> {
+		match self {
+			Transfer::Sending(upload) => Some((
+				format!("{}{} ", FILE_SENDING, name_shown(upload.offer())),
+				upload.progress(),
+			)),
+			Transfer::Receiving(download) => Some((
+				format!("{}{} ", FILE_RECEIVING, name_shown(download.offer())),
+				download.progress(),
+			)),
+			_ => None,
+		}
+	}
+}
+
+/// Get file name safe to put on the terminal, as it may well come from the subject
+fn name_shown(offer: &FileOffer) -> String {
+	husky::sanitize(&offer.name, false).0
+}
+
+/// Get human-readable file size
+pub fn size_describe(bytes: u64) -> String {
+	match bytes {
+		0..=1023 => format!("{} B", bytes),
+		1024..=1048575 => format!("{:.1} KiB", bytes as f64 / 1024.0),
+		_ => format!("{:.1} MiB", bytes as f64 / 1048576.0),
+	}
+}
+
+/// Get prompt for the file offered by the subject (if there's one)
+pub fn offer_prompt(app: &App) -> Option<String> {
+	match (&app.transfer, &app.state) {
+		(
+			Some(Transfer::Offered(offer)),
+			AppState::Chat(Chat {
+				state: ChatState::Tied(subject),
+			}),
+		) => Some(format!(
+			"{} {} {} ({}): {}",
+			subject,
+			FILE_OFFERED_BY,
+			name_shown(offer),
+			size_describe(offer.size),
+			FILE_OFFER_HINT
+		)),
+		_ => None,
+	}
+}
+
+/// Offer file at `path` to the tie subject
+pub unsafe fn offer(path: &str) {
+	if APP.transfer.is_some() {
+		APP.chat_notice = Some(FILE_FAULT_BUSY.to_string());
+		return;
+	}
+	let path = match path.strip_prefix("~/") {
+		Some(rest) => env::var("HOME")
+			.map(|home| PathBuf::from(home).join(rest))
+			.unwrap_or_else(|_| PathBuf::from(path)),
+		None => PathBuf::from(path),
+	};
+	// The subject would decline it anyway, and it'd take just as much memory here
+	if let (Ok(metadata), Some(limit)) = (fs::metadata(&path), APP.config.max_file_size()) {
+		if metadata.len() > limit {
+			APP.chat_notice = Some(format!("{} ({})", FILE_FAULT_TOO_LARGE, size_describe(limit)));
+			return;
+		}
+	}
+	match Upload::open(&path) {
+		Ok(upload) => {
+			APP.client_do(|c| c.offer_file(upload.offer()));
+			APP.chat_notice = Some(format!("{}: {}", FILE_AWAITING, name_shown(upload.offer())));
+			APP.transfer = Some(Transfer::Offering(upload));
+		}
+		Err(err) => APP.chat_notice = Some(format!("{}: {}", FILE_OPEN_FAULT, err)),
+	}
+}
+
+/// Accept file offered by the tie subject
+pub unsafe fn accept() {
+	if let Some(Transfer::Offered(offer)) = &APP.transfer {
+		APP.client_do(|c| c.accept_file());
+		APP.transfer = Some(Transfer::Receiving(Download::new(offer.clone())));
+	} else {
+		APP.chat_notice = Some(FILE_FAULT_NO_OFFER.to_string())
+	}
+}
+
+/// Decline offered file, or cancel the transfer in progress
+pub unsafe fn decline() {
+	if APP.transfer.take().is_some() {
+		APP.client_do(|c| c.decline_file());
+		APP.chat_notice = Some(FILE_CANCELLED.to_string());
+	} else {
+		APP.chat_notice = Some(FILE_FAULT_IDLE.to_string())
+	}
+}
+
+/// Act on file offered by the tie subject, declining it if another transfer is underway or if it's too large
+pub unsafe fn on_offer(offer: FileOffer) {
+	if APP.transfer.is_some() {
+		APP.client_do(|c| c.decline_file());
+	} else if APP.config.max_file_size().is_some_and(|limit| offer.size > limit) {
+		APP.client_do(|c| c.decline_file());
+		APP.chat_notice = Some(format!(
+			"{} {} ({})",
+			FILE_OFFER_TOO_LARGE,
+			name_shown(&offer),
+			size_describe(offer.size)
+		));
+	} else {
+		APP.transfer = Some(Transfer::Offered(offer));
+	}
+}
+
+/// Start sending offered file once the tie subject accepts it
+pub unsafe fn on_accept() {
+	if let Some(Transfer::Offering(upload)) = &APP.transfer {
+		let mut upload = upload.clone();
+		if let Some(chunk) = upload.next_chunk() {
+			APP.client_do(|c| c.send_file_chunk(chunk));
+		}
+		APP.chat_notice = None;
+		APP.transfer = Some(Transfer::Sending(upload));
+	}
+}
+
+/// Drop the transfer declined or cancelled by the tie subject
+pub unsafe fn on_decline() {
+	APP.chat_notice = match APP.transfer.take() {
+		Some(Transfer::Offering(_)) => Some(FILE_DECLINED.to_string()),
+		Some(_) => Some(FILE_CANCELLED.to_string()),
+		None => return,
+	};
+}
+
+/// Send next chunk once the tie subject got the previous one
+pub unsafe fn on_ack() {
+	if let Some(Transfer::Sending(upload)) = &mut APP.transfer {
+		if let Some(chunk) = upload.next_chunk() {
+			APP.client_do(|c| c.send_file_chunk(chunk));
+		}
+	}
+}
+
+/// Collect received chunk, saving the file once it's complete and intact
+pub unsafe fn on_chunk(chunk: &[u8]) {
+	let download = match &mut APP.transfer {
+		Some(Transfer::Receiving(download)) => download,
+		_ => return,
+	};
+	if !download.push(chunk) {
+		APP.client_do(|c| c.ack_file_chunk());
+		return;
+	}
+	let verified = download.verify();
+	APP.client_do(|c| c.file_done(verified));
+	APP.chat_notice = Some(if verified {
+		match download.save(&APP.config.download_dir()) {
+			Ok(path) => format!("{} {}", FILE_SAVED, husky::sanitize(&path.display().to_string(), false).0),
+			Err(err) => format!("{}: {}", FILE_SAVE_FAULT, err),
+		}
+	} else {
+		format!("{} {}", FILE_CORRUPTED, name_shown(download.offer()))
+	});
+	APP.transfer = None;
+}
+
+/// Report the tie subject's verdict on the file sent
+pub unsafe fn on_done(verified: bool) {
+	if let Some(Transfer::Sending(upload)) = APP.transfer.take() {
+		APP.chat_notice = Some(format!(
+			"{} {}",
+			if verified {
+				FILE_SENT
+			} else {
+				FILE_SENT_CORRUPTED
+			},
+			name_shown(upload.offer())
+		));
+	}
+}
@@ -0,0 +1,64 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2021
+
+***************************/
+
+//! Single-character flags every protocol frame starts with
+
+pub const TX_AUTH_FLAG: 
+	char = 'A';
+pub const TX_DROPME_FLAG: 
+	char = 'X';
+pub const TX_TIE_INIT_FLAG: 
+	char = 'T';
+pub const RX_AUTH_OK_FLAG: 
+	char = 'O';
+pub const RX_AUTH_FAULT_FLAG: 
+	char = 'D';
+pub const RX_AUTH_FAULT_OVERAUTH_FLAG:
+	char = 'I';
+pub const RX_TIE_OK_FLAG:
+	char = 'S';
+pub const RX_TIE_OK_WAIT_FLAG:
+	char = 'W';
+pub const RX_TIE_FAULT_NOUSER_FLAG:
+	char = 'N';
+pub const RX_TIE_FAULT_SELFTIE_FLAG:
+	char = 'M';
+pub const RX_TIE_FAULT_OVERTIE_FLAG:
+	char = 'R';
+pub const RXTX_UNTIE_FLAG:
+	char = 'C';
+pub const RXTX_OK_FLAG:
+	char = 'Y';
+pub const RXTX_FAULT_FLAG: 
+	char = 'E';
+pub const RXTX_MESSAGE_FLAG: 
+	char = 'B';
+pub const MESSAGE_TIME_ATTR:
+	char = '@';
+pub const MESSAGE_TTL_ATTR:
+	char = '~';
+pub const RXTX_STATUS_FLAG:
+	char = 'P';
+pub const STATUS_ONLINE_ATTR:
+	char = 'O';
+pub const STATUS_AWAY_ATTR:
+	char = 'A';
+pub const RXTX_FILE_OFFER_FLAG:
+	char = 'F';
+pub const RXTX_FILE_ACCEPT_FLAG:
+	char = 'G';
+pub const RXTX_FILE_DECLINE_FLAG:
+	char = 'H';
+pub const RXTX_FILE_CHUNK_FLAG:
+	char = 'K';
+pub const RXTX_FILE_ACK_FLAG:
+	char = 'L';
+pub const RXTX_FILE_DONE_FLAG:
+	char = 'J';
+pub const FILE_VERIFIED_ATTR:
+	char = 'Y';
+pub const FILE_CORRUPTED_ATTR:
+	char = 'N';
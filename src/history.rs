@@ -0,0 +1,211 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use argon2::Argon2;
+use chacha20poly1305::{
+	aead::{Aead, KeyInit},
+	XChaCha20Poly1305, XNonce,
+};
+use rand::RngCore;
+use serde::{de::DeserializeOwned, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+	fmt, fs, io,
+	path::{Path, PathBuf},
+	time,
+};
+
+/// Marks the beginning of every history file (bump on format change)
+const MAGIC: &[u8] = b"HSKYH1";
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+#[derive(Debug)]
+pub enum HistoryError {
+	Io(io::Error),
+	Corrupted,
+	BadPassphrase,
+}
+
+impl fmt::Display for HistoryError {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		match self {
+			HistoryError::Io(err) => write!(f, "{}", err),
+			HistoryError::Corrupted => write!(f, "history file is corrupted"),
+			HistoryError::BadPassphrase => write!(f, "wrong passphrase"),
+		}
+	}
+}
+
+impl From<io::Error> for HistoryError {
+	fn from(err: io::Error) -> HistoryError {
+		HistoryError::Io(err)
+	}
+}
+
+/// Encrypted on-disk message history of a single tie
+pub struct History {
+	path: PathBuf,
+	salt: [u8; SALT_LEN],
+	cipher: XChaCha20Poly1305,
+}
+
+impl History {
+	/// Open history of `owner`'s conversations with `subject`, decrypting existing records (if any)
+	pub fn open<T: DeserializeOwned>(
+		dir: &Path,
+		owner: &str,
+		subject: &str,
+		passphrase: &str,
+	) -> Result<(History, Vec<T>), HistoryError> {
+		let path = dir.join(file_name(owner, subject));
+		if !path.exists() {
+			let mut salt = [0u8; SALT_LEN];
+			rand::thread_rng().fill_bytes(&mut salt);
+			let history = History {
+				cipher: History::cipher(passphrase, &salt)?,
+				path,
+				salt,
+			};
+			return Ok((history, Vec::new()));
+		}
+		let raw = fs::read(&path)?;
+		let header_len = MAGIC.len() + SALT_LEN + NONCE_LEN;
+		if raw.len() < header_len || &raw[..MAGIC.len()] != MAGIC {
+			return Err(HistoryError::Corrupted);
+		}
+		let mut salt = [0u8; SALT_LEN];
+		salt.copy_from_slice(&raw[MAGIC.len()..MAGIC.len() + SALT_LEN]);
+		let nonce = XNonce::from_slice(&raw[MAGIC.len() + SALT_LEN..header_len]);
+		let cipher = History::cipher(passphrase, &salt)?;
+		let plain = cipher
+			.decrypt(nonce, &raw[header_len..])
+			.map_err(|_| HistoryError::BadPassphrase)?;
+		let records = serde_json::from_slice(&plain).map_err(|_| HistoryError::Corrupted)?;
+		Ok((History { path, salt, cipher }, records))
+	}
+	/// Encrypt and write records to disk, replacing whatever was there.
+	/// Records are written to a temporary file first, so that a crash midway doesn't wipe the history
+	pub fn save<T: Serialize>(&self, records: &[T]) -> Result<(), HistoryError> {
+		let plain = serde_json::to_vec(records).map_err(|_| HistoryError::Corrupted)?;
+		let mut nonce = [0u8; NONCE_LEN];
+		rand::thread_rng().fill_bytes(&mut nonce);
+		let encrypted = self
+			.cipher
+			.encrypt(XNonce::from_slice(&nonce), plain.as_ref())
+			.map_err(|_| HistoryError::Corrupted)?;
+		let mut raw = Vec::with_capacity(MAGIC.len() + SALT_LEN + NONCE_LEN + encrypted.len());
+		raw.extend_from_slice(MAGIC);
+		raw.extend_from_slice(&self.salt);
+		raw.extend_from_slice(&nonce);
+		raw.extend_from_slice(&encrypted);
+		if let Some(parent) = self.path.parent() {
+			fs::create_dir_all(parent)?;
+		}
+		let temp = self.path.with_extension("tmp");
+		fs::write(&temp, raw)?;
+		fs::rename(&temp, &self.path)?;
+		Ok(())
+	}
+	/// Derive encryption key from passphrase
+	fn cipher(passphrase: &str, salt: &[u8]) -> Result<XChaCha20Poly1305, HistoryError> {
+		let mut key = [0u8; 32];
+		Argon2::default()
+			.hash_password_into(passphrase.as_bytes(), salt, &mut key)
+			.map_err(|_| HistoryError::BadPassphrase)?;
+		Ok(XChaCha20Poly1305::new(&key.into()))
+	}
+}
+
+/// Get name of the history file of `owner`'s conversations with `subject`.
+/// Names are hashed so that the history directory itself doesn't reveal any contacts
+fn file_name(owner: &str, subject: &str) -> String {
+	let hash = Sha256::digest(format!("{}\0{}", owner, subject).as_bytes());
+	hash.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Whether file is a history file (or one left half-written by [`History::save`]), judging by its name
+fn is_history_file(name: &str) -> bool {
+	let hash = name.strip_suffix(".tmp").unwrap_or(name);
+	hash.len() == 64 && hash.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Remove history files which weren't touched for longer than `retention`, leaving anything else in `dir` alone
+pub fn prune(dir: &Path, retention: time::Duration) -> io::Result<()> {
+	let entries = match fs::read_dir(dir) {
+		Ok(ok) => ok,
+		Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(()),
+		Err(err) => return Err(err),
+	};
+	for entry in entries {
+		let entry = entry?;
+		if !is_history_file(&entry.file_name().to_string_lossy()) {
+			continue;
+		}
+		let modified = entry.metadata()?.modified()?;
+		if modified.elapsed().unwrap_or_default() > retention {
+			fs::remove_file(entry.path())?;
+		}
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn round_trip() {
+		let dir = std::env::temp_dir().join(format!("husky-history-round-trip-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let (history, records) = History::open::<String>(&dir, "alice", "bob", "secret").unwrap();
+		assert!(records.is_empty());
+		history.save(&["Hi".to_string(), "Hello".to_string()]).unwrap();
+		let (_, records) = History::open::<String>(&dir, "alice", "bob", "secret").unwrap();
+		assert_eq!(records, ["Hi", "Hello"]);
+		// Nothing but the history file itself is left behind
+		assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+		let (_, records) = History::open::<String>(&dir, "alice", "carol", "secret").unwrap();
+		assert!(records.is_empty());
+		fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn wrong_passphrase() {
+		let dir = std::env::temp_dir().join(format!("husky-history-wrong-passphrase-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let (history, _) = History::open::<String>(&dir, "alice", "bob", "secret").unwrap();
+		history.save(&["Hi".to_string()]).unwrap();
+		assert!(matches!(
+			History::open::<String>(&dir, "alice", "bob", "guess"),
+			Err(HistoryError::BadPassphrase)
+		));
+		fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn stale_files_are_pruned() {
+		let dir = std::env::temp_dir().join(format!("husky-history-prune-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let day = time::Duration::from_secs(24 * 60 * 60);
+		let touch = |name: &str, age: time::Duration| {
+			let file = fs::File::create(dir.join(name)).unwrap();
+			file.set_modified(time::SystemTime::now() - age).unwrap();
+		};
+		let (stale, fresh) = (file_name("alice", "bob"), file_name("alice", "carol"));
+		touch(&stale, day * 3);
+		touch(&format!("{}.tmp", fresh), day * 3);
+		touch(&fresh, time::Duration::ZERO);
+		touch("notes.txt", day * 3);
+		prune(&dir, day).unwrap();
+		assert!(!dir.join(&stale).exists());
+		assert!(!dir.join(format!("{}.tmp", fresh)).exists());
+		assert!(dir.join(&fresh).exists());
+		assert!(dir.join("notes.txt").exists());
+		prune(&dir.join("missing"), time::Duration::ZERO).unwrap();
+		fs::remove_dir_all(&dir).ok();
+	}
+}
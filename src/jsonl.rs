@@ -0,0 +1,211 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use crate::strings::ui::*;
+use crate::{preconnect, socket_connect, ChatMessage, MessageState, APP};
+use chrono::{DateTime, Utc};
+use husky::{Client, Presence};
+use serde::{Deserialize, Serialize};
+use std::{
+	io::{self, Write},
+	time::Duration,
+};
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+/// Everything that is written to stdout, one object per line
+#[derive(Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event {
+	AuthOk,
+	AuthFault,
+	AuthFaultOverauth,
+	TieOk,
+	TieWait,
+	TieFaultNouser,
+	TieFaultSelftie,
+	TieFaultOvertie,
+	Untie,
+	Message {
+		sender: String,
+		text: String,
+		state: MessageState,
+		time: DateTime<Utc>,
+		/// Seconds after being sent the message is to be removed, if it's ephemeral
+		#[serde(skip_serializing_if = "Option::is_none")]
+		ttl: Option<u64>,
+	},
+	Status {
+		away: bool,
+		message: String,
+	},
+	Ok,
+	Fault,
+	Unknown {
+		frame: String,
+	},
+	Error {
+		message: String,
+	},
+	Disconnected,
+}
+
+/// Everything that is accepted from stdin, one object per line
+#[derive(Deserialize)]
+#[serde(tag = "cmd", rename_all = "snake_case")]
+enum Command {
+	Tie { user: String },
+	Send {
+		message: String,
+		#[serde(default)]
+		ttl: Option<u64>,
+	},
+	Untie,
+	Status { message: String },
+	Quit,
+}
+
+/// Translate event received from server into its JSON representation (`None` if it has none).
+/// Message is delivered if it's the echo of one of `sent` (which is then forgotten), and received otherwise
+fn translate(event: husky::Event, username: &str, sent: &mut Vec<String>) -> Option<Event> {
+	Some(match event {
+		husky::Event::AuthOk => Event::AuthOk,
+		husky::Event::AuthFault => Event::AuthFault,
+		husky::Event::AuthFaultOverauth => Event::AuthFaultOverauth,
+		husky::Event::TieOk => Event::TieOk,
+		husky::Event::TieWait => Event::TieWait,
+		husky::Event::TieFaultNoUser => Event::TieFaultNouser,
+		husky::Event::TieFaultSelfTie => Event::TieFaultSelftie,
+		husky::Event::TieFaultOverTie => Event::TieFaultOvertie,
+		husky::Event::Untie => Event::Untie,
+		husky::Event::Message { sender, text, time, ttl } => {
+			let mut m = ChatMessage::received(&sender, &text, time, ttl);
+			// The subject may call themselves anything, so only what's been sent from here is echoed back
+			if let (true, Some(i)) = (sender == username, sent.iter().position(|s| *s == text)) {
+				sent.remove(i);
+				m.state = MessageState::Delivered;
+			}
+			Event::Message {
+				sender: m.sender,
+				text: m.text,
+				state: m.state,
+				time: m.time,
+				ttl: ttl.map(|ttl| ttl.as_secs()),
+			}
+		}
+		husky::Event::Status(status) => Event::Status {
+			away: status.presence == Presence::Away,
+			message: status.message,
+		},
+		husky::Event::Ok => Event::Ok,
+		husky::Event::Fault => Event::Fault,
+		husky::Event::Unknown(frame) => Event::Unknown { frame },
+		// File transfers are only supported in the interactive client
+		husky::Event::FileOffer(_)
+		| husky::Event::FileAccept
+		| husky::Event::FileDecline
+		| husky::Event::FileChunk(_)
+		| husky::Event::FileAck
+		| husky::Event::FileDone { .. } => return None,
+	})
+}
+
+/// Act on command read from stdin, noting messages `sent` (returns `false` if it's time to quit)
+unsafe fn perform(client: &Client, command: Command, sent: &mut Vec<String>) -> Result<bool, husky::Error> {
+	match command {
+		Command::Tie { user } => client.tie(&user)?,
+		Command::Send { message, ttl } => {
+			match ttl {
+				None => client.send(&message)?,
+				Some(secs) => client.send_ephemeral(&message, Duration::from_secs(secs))?,
+			}
+			sent.push(message)
+		}
+		Command::Untie => client.untie()?,
+		Command::Status { message } => {
+			APP.status.message = message;
+			client.set_status(&APP.status)?
+		}
+		Command::Quit => return Ok(false),
+	}
+	Ok(true)
+}
+
+/// Write event to stdout, failing if nobody reads it anymore
+fn emit(event: Event) -> io::Result<()> {
+	let mut out = io::stdout().lock();
+	serde_json::to_writer(&mut out, &event)?;
+	writeln!(out)?;
+	out.flush()
+}
+
+/// Bridge socket and stdio until either of them is closed
+pub async unsafe fn run() -> Result<(), String> {
+	if let Err(fault) = preconnect().await {
+		emit(Event::Error {
+			message: fault.to_string(),
+		})
+		.ok();
+		return Err(fault.to_string());
+	}
+	let (mut client, mut events) = socket_connect()
+		.await
+		.map_err(|_| AUTH_JOB_CONNECT_FAULT.to_string())?;
+	client
+		.auth(&APP.server.key, APP.user_key.as_ref().unwrap())
+		.map_err(|_| AUTH_JOB_CONNECT_FAULT.to_string())?;
+	let username = APP.user_key.as_ref().unwrap().username.clone();
+	let mut sent = Vec::new();
+	let mut lines = BufReader::new(tokio::io::stdin()).lines();
+	loop {
+		tokio::select! {
+			event = events.next() => match event {
+				Some(Ok(event)) => {
+					if let Some(event) = translate(event, &username, &mut sent) {
+						// Nobody is listening anymore (say, the consumer closed the pipe)
+						if emit(event).is_err() {
+							break;
+						}
+					}
+				}
+				Some(Err(err)) => {
+					if emit(Event::Error { message: err.to_string() }).is_err() {
+						break;
+					}
+				}
+				None => {
+					emit(Event::Disconnected).ok();
+					return Ok(());
+				}
+			},
+			line = lines.next_line() => {
+				let proceed = match line {
+					Ok(Some(line)) if line.trim().is_empty() => continue,
+					Ok(Some(line)) => match serde_json::from_str::<Command>(&line) {
+						Ok(command) => perform(&client, command, &mut sent),
+						Err(err) => {
+							if emit(Event::Error { message: err.to_string() }).is_err() {
+								break;
+							}
+							continue;
+						}
+					},
+					_ => Ok(false),
+				};
+				match proceed {
+					Ok(true) => (),
+					Ok(false) => break,
+					Err(err) => {
+						emit(Event::Error { message: err.to_string() }).ok();
+						return Err(err.to_string());
+					}
+				}
+			}
+		}
+	}
+	client.drop_me().ok();
+	while events.next().await.is_some() {}
+	Ok(())
+}
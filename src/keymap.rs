@@ -0,0 +1,306 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use crate::strings::{ui::*, Localized};
+use crossterm::event::{KeyCode, KeyEvent, KeyModifiers};
+use serde::Deserialize;
+use std::{collections::BTreeMap, convert::TryFrom, fmt};
+
+/// Something the user can do with a key press
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+	FocusPrev,
+	FocusNext,
+	Submit,
+	ScrollUp,
+	ScrollDown,
+	Untie,
+	SetStatus,
+	Export,
+	SaveLog,
+	ToggleInvisible,
+	Complete,
+	Compose,
+	Cancel,
+	Help,
+	Quit,
+}
+
+impl Action {
+	/// Every action, in the order they're listed in help
+	pub const ALL: [Action; 15] = [
+		Action::FocusPrev,
+		Action::FocusNext,
+		Action::Submit,
+		Action::ScrollUp,
+		Action::ScrollDown,
+		Action::Untie,
+		Action::SetStatus,
+		Action::Export,
+		Action::SaveLog,
+		Action::ToggleInvisible,
+		Action::Complete,
+		Action::Compose,
+		Action::Cancel,
+		Action::Help,
+		Action::Quit,
+	];
+	fn description(&self) -> Localized {
+		match self {
+			Action::FocusPrev => HELP_FOCUS_PREV,
+			Action::FocusNext => HELP_FOCUS_NEXT,
+			Action::Submit => HELP_SUBMIT,
+			Action::ScrollUp => HELP_SCROLL_UP,
+			Action::ScrollDown => HELP_SCROLL_DOWN,
+			Action::Untie => HELP_UNTIE,
+			Action::SetStatus => HELP_SET_STATUS,
+			Action::Export => HELP_EXPORT,
+			Action::SaveLog => HELP_SAVE_LOG,
+			Action::ToggleInvisible => HELP_TOGGLE_INVISIBLE,
+			Action::Complete => HELP_COMPLETE,
+			Action::Compose => HELP_COMPOSE,
+			Action::Cancel => HELP_CANCEL,
+			Action::Help => HELP_HELP,
+			Action::Quit => HELP_QUIT,
+		}
+	}
+	fn default_keys(&self) -> &'static [&'static str] {
+		match self {
+			Action::FocusPrev => &["up"],
+			Action::FocusNext => &["down"],
+			Action::Submit => &["enter"],
+			Action::ScrollUp => &["pageup"],
+			Action::ScrollDown => &["pagedown"],
+			Action::Untie => &["ctrl+u"],
+			Action::SetStatus => &["f2"],
+			Action::Export => &["f3"],
+			Action::SaveLog => &["f4"],
+			Action::ToggleInvisible => &["f5"],
+			Action::Complete => &["tab"],
+			Action::Compose => &["ctrl+e"],
+			Action::Cancel => &["esc"],
+			Action::Help => &["f1"],
+			Action::Quit => &["f9", "ctrl+c"],
+		}
+	}
+}
+
+/// A key with its modifiers, written like `ctrl+k`, `f9` or `pageup` in config
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(try_from = "String")]
+pub struct Key {
+	code: KeyCode,
+	modifiers: KeyModifiers,
+}
+
+impl TryFrom<String> for Key {
+	type Error = String;
+	fn try_from(value: String) -> Result<Key, String> {
+		let lowercase = value.to_lowercase();
+		let mut parts: Vec<&str> = lowercase.split('+').collect();
+		// "ctrl++" binds the plus key itself
+		if lowercase.ends_with("++") {
+			parts.truncate(parts.len() - 2);
+			parts.push("+");
+		}
+		let name = parts.pop().unwrap_or_default();
+		let mut modifiers = KeyModifiers::NONE;
+		for part in parts {
+			modifiers |= match part {
+				"ctrl" => KeyModifiers::CONTROL,
+				"alt" => KeyModifiers::ALT,
+				"shift" => KeyModifiers::SHIFT,
+				_ => return Err(format!("{} '{}'", KEY_FAULT_MODIFIER, part)),
+			}
+		}
+		let code = match name {
+			"up" => KeyCode::Up,
+			"down" => KeyCode::Down,
+			"left" => KeyCode::Left,
+			"right" => KeyCode::Right,
+			"enter" => KeyCode::Enter,
+			"tab" => KeyCode::Tab,
+			"esc" => KeyCode::Esc,
+			"backspace" => KeyCode::Backspace,
+			"delete" => KeyCode::Delete,
+			"insert" => KeyCode::Insert,
+			"home" => KeyCode::Home,
+			"end" => KeyCode::End,
+			"pageup" => KeyCode::PageUp,
+			"pagedown" => KeyCode::PageDown,
+			"space" => KeyCode::Char(' '),
+			_ => match (name.strip_prefix('f').map(str::parse::<u8>), name.chars().count()) {
+				(Some(Ok(n)), _) if (1..=12).contains(&n) => KeyCode::F(n),
+				(_, 1) => KeyCode::Char(name.chars().next().unwrap()),
+				_ => return Err(format!("{} '{}'", KEY_FAULT_NAME, value)),
+			},
+		};
+		Ok(Key { code, modifiers })
+	}
+}
+
+impl Key {
+	fn parse(value: &str) -> Key {
+		Key::try_from(value.to_string()).unwrap()
+	}
+	fn matches(&self, event: &KeyEvent) -> bool {
+		let mut modifiers = event.modifiers;
+		if let KeyCode::Char(_) = event.code {
+			// Shifted characters come already uppercased
+			modifiers.remove(KeyModifiers::SHIFT);
+		}
+		let code = match event.code {
+			KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+			code => code,
+		};
+		code == self.code && modifiers == self.modifiers
+	}
+	/// Whether pressing the key would otherwise type something
+	fn types(&self) -> bool {
+		matches!(self.code, KeyCode::Char(_)) && (self.modifiers - KeyModifiers::SHIFT).is_empty()
+	}
+}
+
+impl Key {
+	/// Get human-readable name, like `CTRL + K`
+	fn name(&self) -> String {
+		let mut name = String::new();
+		for (modifier, modifier_name) in [
+			(KeyModifiers::CONTROL, "CTRL + "),
+			(KeyModifiers::ALT, "ALT + "),
+			(KeyModifiers::SHIFT, "SHIFT + "),
+		] {
+			if self.modifiers.contains(modifier) {
+				name.push_str(modifier_name)
+			}
+		}
+		match self.code {
+			KeyCode::Up => name.push_str("ARROW UP"),
+			KeyCode::Down => name.push_str("ARROW DOWN"),
+			KeyCode::Left => name.push_str("ARROW LEFT"),
+			KeyCode::Right => name.push_str("ARROW RIGHT"),
+			KeyCode::PageUp => name.push_str("PAGE UP"),
+			KeyCode::PageDown => name.push_str("PAGE DOWN"),
+			KeyCode::Char(' ') => name.push_str("SPACE"),
+			KeyCode::Char(c) => name.push(c.to_ascii_uppercase()),
+			KeyCode::F(n) => name.push_str(&format!("F{}", n)),
+			code => name.push_str(&format!("{:?}", code).to_uppercase()),
+		}
+		name
+	}
+}
+
+impl fmt::Display for Key {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		write!(f, "[{}]", self.name())
+	}
+}
+
+/// Keys bound to every action
+#[derive(Clone)]
+pub struct Keymap {
+	bindings: Vec<(Key, Action)>,
+}
+
+impl Keymap {
+	/// Get nullable const-friendly Keymap instance
+	pub const fn null() -> Keymap {
+		Keymap {
+			bindings: Vec::new(),
+		}
+	}
+	/// Bind default keys to actions not bound in `custom`. Custom bindings take precedence over default ones
+	pub fn new(custom: &BTreeMap<Action, Vec<Key>>) -> Keymap {
+		let mut bindings: Vec<(Key, Action)> = Vec::new();
+		for (action, keys) in custom {
+			bindings.extend(keys.iter().map(|key| (*key, *action)))
+		}
+		for action in Action::ALL {
+			if custom.contains_key(&action) {
+				continue;
+			}
+			for key in action.default_keys().iter().map(|key| Key::parse(key)) {
+				if !bindings.iter().any(|(bound, _)| *bound == key) {
+					bindings.push((key, action))
+				}
+			}
+		}
+		Keymap { bindings }
+	}
+	/// Get action bound to the key pressed. Keys that type characters are left alone while the user is `typing`
+	pub fn action(&self, event: &KeyEvent, typing: bool) -> Option<Action> {
+		self.bindings
+			.iter()
+			.find(|(key, _)| key.matches(event) && !(typing && key.types()))
+			.map(|(_, action)| *action)
+	}
+	/// Fill every `{}` in `template` with names of the keys bound to the corresponding one of `actions`
+	pub fn prompt(&self, template: &str, actions: &[Action]) -> String {
+		let mut prompt = template.to_string();
+		for action in actions {
+			let keys: Vec<String> = self
+				.bindings
+				.iter()
+				.filter(|(_, bound)| bound == action)
+				.map(|(key, _)| key.name())
+				.collect();
+			prompt = prompt.replacen("{}", &keys.join(" / "), 1);
+		}
+		prompt
+	}
+	/// Get usage instructions listing the keys bound to every action
+	pub fn help(&self) -> String {
+		self.help_for(&Action::ALL)
+	}
+	/// Get usage instructions listing the keys bound to `actions`
+	pub fn help_for(&self, actions: &[Action]) -> String {
+		let mut help = USAGE_INSTRUCTIONS.to_string();
+		for action in actions {
+			let keys: Vec<String> = self
+				.bindings
+				.iter()
+				.filter(|(_, bound)| bound == action)
+				.map(|(key, _)| key.to_string())
+				.collect();
+			if !keys.is_empty() {
+				help.push_str(&format!("\n\t{} – {}", keys.join(" / "), action.description()));
+			}
+		}
+		help
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn keys_are_parsed() {
+		assert_eq!(Key::parse("ctrl+k").to_string(), "[CTRL + K]");
+		assert_eq!(Key::parse("F9").to_string(), "[F9]");
+		assert_eq!(Key::parse("pageup").to_string(), "[PAGE UP]");
+		assert_eq!(Key::parse("alt++").to_string(), "[ALT + +]");
+		assert!(Key::try_from("hyper+k".to_string()).is_err());
+		assert!(Key::try_from("f13".to_string()).is_err());
+		assert!(Key::try_from("kk".to_string()).is_err());
+	}
+
+	#[test]
+	fn custom_bindings_take_precedence() {
+		let mut custom = BTreeMap::new();
+		custom.insert(Action::FocusNext, vec![Key::parse("j"), Key::parse("down")]);
+		custom.insert(Action::Quit, vec![Key::parse("ctrl+q")]);
+		let keymap = Keymap::new(&custom);
+		assert_eq!(keymap.action(&KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE), false), Some(Action::FocusNext));
+		assert_eq!(keymap.action(&KeyEvent::new(KeyCode::Char('j'), KeyModifiers::NONE), true), None);
+		assert_eq!(keymap.action(&KeyEvent::new(KeyCode::Char('q'), KeyModifiers::CONTROL), true), Some(Action::Quit));
+		assert_eq!(keymap.action(&KeyEvent::new(KeyCode::F(9), KeyModifiers::NONE), false), None);
+		assert_eq!(keymap.action(&KeyEvent::new(KeyCode::Up, KeyModifiers::NONE), true), Some(Action::FocusPrev));
+		assert!(keymap.help().contains("\t[J] / [ARROW DOWN] – "));
+		assert_eq!(keymap.prompt("[ {} to quit, {} to save ]", &[Action::Quit, Action::SaveLog]), "[ CTRL + Q to quit, F4 to save ]");
+	}
+}
@@ -0,0 +1,40 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+//! Husky client library: the protocol spoken by `server.php` and an async [`Client`] for it,
+//! so that bots and alternative frontends don't have to reimplement any of that.
+//!
+//! ```no_run
+//! # async fn run() -> Result<(), husky::Error> {
+//! let endpoint = husky::Endpoint::new("example.com", "8000");
+//! husky::Client::preconnect(&endpoint).await?;
+//! let (mut client, mut events) = husky::Client::connect(&endpoint).await?;
+//! client.auth("server key", &husky::UserKey::new("bot:password".to_string()))?;
+//! while let Some(event) = events.next().await {
+//!     match event? {
+//!         husky::Event::AuthOk => client.tie("alice")?,
+//!         husky::Event::TieOk => client.send("Hi there")?,
+//!         _ => (),
+//!     }
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+#[rustfmt::skip]
+#[allow(dead_code)]
+pub mod flags;
+mod protocol;
+mod sanitize;
+mod trace;
+mod transfer;
+
+pub use client::{Client, Endpoint, Error, Events};
+pub use protocol::{Event, FileOffer, Presence, Status, UserKey};
+pub use sanitize::sanitize;
+pub use trace::{load as load_trace, redact, Direction, Trace, TraceRecord, REDACTED};
+pub use transfer::{Download, Upload, CHUNK_SIZE};
@@ -0,0 +1,78 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use chrono::{DateTime, Local};
+use std::{
+	fs, io,
+	path::{Path, PathBuf},
+};
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum LogLevel {
+	Info,
+	Success,
+	/// Something unexpected that doesn't break the job
+	Warning,
+	Fault,
+}
+
+impl LogLevel {
+	fn name(&self) -> &'static str {
+		match self {
+			LogLevel::Info => "INFO",
+			LogLevel::Success => "SUCCESS",
+			LogLevel::Warning => "WARNING",
+			LogLevel::Fault => "FAULT",
+		}
+	}
+}
+
+/// A single job log line is stored here
+#[derive(PartialEq, Clone, Debug)]
+pub struct LogEntry {
+	pub time: DateTime<Local>,
+	pub level: LogLevel,
+	pub message: String,
+}
+
+impl LogEntry {
+	pub fn new(level: LogLevel, message: &str) -> LogEntry {
+		LogEntry {
+			time: Local::now(),
+			level,
+			message: message.to_string(),
+		}
+	}
+	/// Get entry's timestamp the way it's displayed in job log
+	pub fn time_render(&self) -> String {
+		format!("({})", self.time.format("%H:%M:%S"))
+	}
+	/// Get full entry representation to be saved to a file
+	fn render_full(&self) -> String {
+		format!(
+			"{} {:<7} {}",
+			self.time.format("%Y-%m-%d %H:%M:%S%.3f %:z"),
+			self.level.name(),
+			self.message
+		)
+	}
+}
+
+/// Write job log to a new file in `dir`, headed with client version and job title
+pub fn save(dir: &Path, title: &str, entries: &[LogEntry]) -> io::Result<PathBuf> {
+	let path = dir.join(format!(
+		"husky-log-{}.txt",
+		Local::now().format("%Y%m%d-%H%M%S")
+	));
+	let mut out = format!("Husky v{} / {}\n", env!("CARGO_PKG_VERSION"), title);
+	for entry in entries {
+		out.push_str(&entry.render_full());
+		out.push('\n');
+	}
+	fs::create_dir_all(dir)?;
+	fs::write(&path, out)?;
+	Ok(path)
+}
@@ -4,29 +4,52 @@ COPYRIGHT LESTER COVEY (me@lestercovey.ml),
 
 ***************************/
 
-use crate::strings::flags::*;
-use crate::strings::ui::*;
-use chrono::Local;
+use crate::strings::{ui::*, Localized};
+use chrono::{DateTime, Local, NaiveDate, Utc};
 use crossterm::{
-	event::{self, Event, KeyCode, KeyModifiers},
+	cursor::Show,
+	event::{
+		self, DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture, Event, KeyCode,
+		KeyEventKind, KeyModifiers, MouseButton, MouseEvent, MouseEventKind,
+	},
 	execute,
-	terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+	terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen, SetTitle},
+};
+use husky::{Client, Endpoint, Events, Presence, Status, UserKey};
+use keymap::{Action, Keymap};
+use log::{LogEntry, LogLevel};
+use serde::{Deserialize, Serialize};
+use futures_util::FutureExt;
+use std::{
+	error::Error,
+	io, panic, process,
+	sync::atomic::{AtomicBool, Ordering},
+	thread, time,
+	time::Duration,
 };
-use futures_util::{SinkExt, StreamExt};
-use reqwest;
-use std::{error::Error, io, panic, process, thread, time};
-use tokio_tungstenite::{connect_async, tungstenite::Message, MaybeTlsStream, WebSocketStream};
 use tui::{
 	backend::{Backend, CrosstermBackend},
-	layout::{Alignment, Constraint, Corner, Direction, Layout},
-	style::{Color, Style},
-	text::{Span, Spans},
-	widgets::{Block, BorderType, Borders, Gauge, List, ListItem, Paragraph},
-	Frame, Terminal,
+	layout::Rect,
+	Terminal,
 };
-use unicode_width::UnicodeWidthStr;
+use ui::ChatTarget;
+mod cli;
+mod commands;
+mod config;
+mod crash;
+mod editor;
+mod export;
+mod files;
+mod history;
+mod jsonl;
+mod keymap;
+mod log;
+mod notify;
+mod replay;
 mod secure;
 mod strings;
+mod theme;
+mod ui;
 
 #[derive(PartialEq, Clone)]
 enum AppState {
@@ -75,13 +98,101 @@ impl Job {
 			data: Vec::new(),
 		}
 	}
-	unsafe fn log_add(&mut self, msg: &str) {
-		let time = Local::now();
-		let t_string = time.format("%H:%M:%S");
-		// FIXME:
-		// This is the dumbest fucking shit but its beta anyways
-		JOB_LOG.push(format!("({}) {}", t_string, msg));
+}
+
+/// Get human-readable status representation
+fn status_describe(status: &Status) -> String {
+	let presence = match status.presence {
+		Presence::Online => STATUS_ONLINE,
+		Presence::Away => STATUS_AWAY,
+	};
+	if status.message.is_empty() {
+		presence.to_string()
+	} else {
+		format!("{}: {}", presence, husky::sanitize(&status.message, false).0)
+	}
+}
+
+#[derive(PartialEq, Clone, Copy, Default, Serialize, Deserialize, Debug)]
+#[serde(rename_all = "lowercase")]
+enum MessageState {
+	/// Message came from the tie subject
+	#[default]
+	Received,
+	/// User's own message, echoed back by server
+	Delivered,
+	/// User's own message, not echoed back yet
+	Pending,
+}
+
+/// A single chat message is stored here
+#[derive(PartialEq, Clone, Serialize, Deserialize)]
+struct ChatMessage {
+	time: DateTime<Utc>,
+	sender: String,
+	text: String,
+	#[serde(default)]
+	state: MessageState,
+	/// When ephemeral message is to be removed. Such messages are never saved anywhere
+	#[serde(skip)]
+	expires: Option<DateTime<Utc>>,
+}
+
+impl ChatMessage {
+	/// Make message which came from socket, stamped with the time it was sent at if known,
+	/// and with the time it's received at otherwise. It's the subject's unless it's matched with a message sent from here
+	fn received(sender: &str, text: &str, time: Option<DateTime<Utc>>, ttl: Option<Duration>) -> ChatMessage {
+		let time = time.unwrap_or_else(Utc::now);
+		ChatMessage {
+			time,
+			sender: sender.to_string(),
+			text: text.to_string(),
+			state: MessageState::Received,
+			expires: expiry(ttl),
+		}
+	}
+	/// Make user's own message, shown before the server echoes it back
+	fn pending(username: &str, text: &str, ttl: Option<Duration>) -> ChatMessage {
+		let time = Utc::now();
+		ChatMessage {
+			time,
+			sender: username.to_string(),
+			text: text.to_string(),
+			state: MessageState::Pending,
+			expires: expiry(ttl),
+		}
+	}
+	/// Whether message was written by the user
+	fn is_own(&self) -> bool {
+		self.state != MessageState::Received
+	}
+	/// Get local time the message was sent at, formatted to be displayed in chat
+	fn stamp(&self, time_format: &str) -> String {
+		format!("({})", self.time.with_timezone(&Local).format(time_format))
+	}
+	/// Get message representation to be displayed in chat, and whether it had to be made terminal-safe
+	fn render(&self, show_invisible: bool, time_format: &str) -> (String, bool) {
+		let (sender, sender_altered) = husky::sanitize(&self.sender, show_invisible);
+		let (text, text_altered) = husky::sanitize(&self.text, show_invisible);
+		let rendered = if sender.is_empty() {
+			format!("{} {}", self.stamp(time_format), text)
+		} else {
+			format!("{} {}: {}", self.stamp(time_format), sender, text)
+		};
+		(rendered, sender_altered || text_altered)
 	}
+	/// Get local date the message was sent on
+	fn day(&self) -> NaiveDate {
+		self.time.with_timezone(&Local).date_naive()
+	}
+}
+
+/// Get time when message arriving now is to be removed (`None` if it's to be kept).
+/// It's counted from now rather than from when the message was sent, as the sender's clock can't be trusted,
+/// and `ttl` too long to count makes message last till the end of time, but never makes it kept
+fn expiry(ttl: Option<Duration>) -> Option<DateTime<Utc>> {
+	let ttl = chrono::Duration::from_std(ttl?).unwrap_or(chrono::Duration::MAX);
+	Some(Utc::now().checked_add_signed(ttl).unwrap_or(DateTime::<Utc>::MAX_UTC))
 }
 
 /// The chat data is stored here
@@ -103,35 +214,32 @@ impl Chat {
 			state: ChatState::Tied(with_subject),
 		}
 	}
-	unsafe fn messages_add(&self, msg: &str) {
-		let time = Local::now();
-		let t_string = time.format("%H:%M");
-		// FIXME:
-		// This is the dumbest fucking shit (II) but its beta anyways
-		CHAT_MESSAGES.insert(0, format!("({}) {}", t_string, msg));
-	}
-}
-
-/// The user's auth key data is stored here
-#[derive(PartialEq, Clone)]
-struct UserKey {
-	full: String,
-	username: String,
-}
-
-impl UserKey {
-	fn default(with_full: String) -> UserKey {
-		UserKey {
-			full: with_full.clone(),
-			username: with_full.split(":").collect::<Vec<&str>>()[0].to_string(),
-		}
-	}
 }
 
 /// The main application data is stored here
 struct App {
 	server: secure::Server,
+	config: config::Config,
 	user_key: Option<UserKey>,
+	status: Status,
+	status_editing: bool,
+	/// Whether invisible characters in messages are escaped
+	show_invisible: bool,
+	theme: theme::Theme,
+	keymap: Keymap,
+	/// Whether key bindings are listed over everything else
+	help_shown: bool,
+	/// Number of newest messages (in chat) or oldest log entries (in jobs) scrolled out of view
+	scroll: usize,
+	subject_status: Option<Status>,
+	last_input: Option<time::Instant>,
+	history: Option<history::History>,
+	chat_notice: Option<String>,
+	/// Log of the current job
+	job_log: Vec<LogEntry>,
+	/// All messages of the current chat, newest first
+	chat_messages: Vec<ChatMessage>,
+	transfer: Option<files::Transfer>,
 	inputs: [String; 3],
 	input_focus: u8,
 	max_input_focus: u8,
@@ -141,26 +249,67 @@ struct App {
 	// Oh this is the stupidest thing in this script
 	// I just couldn't figure out a way to tame all the async stuff otherwise
 	requested_job: u8,
-	sending_queue: Vec<String>,
-	sending_queue_sent: u8,
-	writer_exists: bool,
+	/// Whether message is to be written in external editor, suspending the interface
+	requested_editor: bool,
+	/// Task running the current job, to be aborted if the job is
+	job_task: Option<tokio::task::AbortHandle>,
+	/// Whether the running job waits for a person (like the subject tying back) rather than the server
+	job_waiting: bool,
+	client: Option<Client>,
+	/// Where to record protocol frames to (if asked to)
+	trace: Option<husky::Trace>,
+	/// Whether frames come from a recorded trace rather than a socket
+	replaying: bool,
+	/// Whether the terminal is focused (always assumed so if it doesn't report focus changes)
+	focused: bool,
+	/// Number of messages arrived since the terminal lost focus
+	unread: usize,
+	/// Latest message to notify the user about
+	notice: Option<notify::Notice>,
+	/// Lifetime of messages the user sends to the current tie subject (`None` to keep them)
+	ttl: Option<Duration>,
 }
 
 impl App {
 	/// Get initial App instance
-	fn initial() -> App {
+	fn initial(config: config::Config) -> App {
 		App {
 			server: secure::Server::default(),
 			user_key: None,
+			status: Status {
+				presence: Presence::Online,
+				message: config.status_message.clone(),
+			},
+			status_editing: false,
+			show_invisible: false,
+			theme: theme::Theme::load(config.theme),
+			keymap: Keymap::new(&config.keys),
+			help_shown: false,
+			scroll: 0,
+			subject_status: None,
+			last_input: Some(time::Instant::now()),
+			history: None,
+			chat_notice: None,
+			job_log: Vec::new(),
+			chat_messages: Vec::new(),
+			transfer: None,
+			config,
 			inputs: ["".to_string(), "".to_string(), "".to_string()],
 			input_focus: 0,
 			max_input_focus: 1,
 			state: AppState::Auth,
 			requested_exit: false,
 			requested_job: 0,
-			sending_queue: Vec::new(),
-			sending_queue_sent: 0,
-			writer_exists: false,
+			requested_editor: false,
+			job_task: None,
+			job_waiting: false,
+			client: None,
+			trace: None,
+			replaying: false,
+			focused: true,
+			unread: 0,
+			notice: None,
+			ttl: None,
 		}
 	}
 	// FIXME:
@@ -174,28 +323,47 @@ impl App {
 				port: String::new(),
 				name: String::new(),
 			},
+			config: config::Config::null(),
 			user_key: None,
+			status: Status {
+				presence: Presence::Online,
+				message: String::new(),
+			},
+			status_editing: false,
+			show_invisible: false,
+			theme: theme::Theme::DEFAULT,
+			keymap: Keymap::null(),
+			help_shown: false,
+			scroll: 0,
+			subject_status: None,
+			last_input: None,
+			history: None,
+			chat_notice: None,
+			job_log: Vec::new(),
+			chat_messages: Vec::new(),
+			transfer: None,
 			inputs: [String::new(), String::new(), String::new()],
 			input_focus: 0,
 			max_input_focus: 1,
 			state: AppState::Auth,
 			requested_exit: false,
 			requested_job: 0,
-			sending_queue: Vec::new(),
-			sending_queue_sent: 0,
-			writer_exists: false,
+			requested_editor: false,
+			job_task: None,
+			job_waiting: false,
+			client: None,
+			trace: None,
+			replaying: false,
+			focused: true,
+			unread: 0,
+			notice: None,
+			ttl: None,
 		}
 	}
 	/// Add text to App's job (if current state is `Job`, otherwise do nothing)
-	unsafe fn job_log_add(&mut self, msg: &str) {
-		// FIXME:
-		// This is imo the only 'real' *unsafe* part of the story
+	fn job_log_add(&mut self, level: LogLevel, msg: &str) {
 		match &self.state {
-			AppState::Job(job) => {
-				let mut job = job.clone();
-				job.log_add(msg);
-				self.state = AppState::Job(job)
-			}
+			AppState::Job(_) => self.job_log.push(LogEntry::new(level, msg)),
 			_ => { /* TODO: Maybe panic? */ }
 		}
 	}
@@ -233,23 +401,92 @@ impl App {
 			_ => (),
 		}
 	}
-	/// Safely add text to App's sending queue
-	fn sending_queue_add(&mut self, msg: String) {
-		if self.sending_queue_sent >= 10 {
-			self.sending_queue = vec![msg]
-		} else {
-			self.sending_queue.push(msg)
+	/// Run something on App's client (if connected, otherwise do nothing)
+	fn client_do(&self, action: impl FnOnce(&Client) -> Result<(), husky::Error>) {
+		if let Some(client) = &self.client {
+			// Failed writes are reported through events, so there's nothing to do here
+			action(client).ok();
 		}
 	}
-	/// Add text to App Chat's messages (if current state is `Chat`, otherwise do nothing)
-	unsafe fn chat_messages_add(&mut self, msg: &str) {
-		// FIXME:
-		// This is imo the only 'real' *unsafe* part of the story
+	/// Change user's presence and let the tie subject know about it
+	fn status_presence_set(&mut self, presence: Presence) {
+		if self.status.presence == presence {
+			return;
+		}
+		self.status.presence = presence;
+		self.status_broadcast();
+	}
+	/// Send user's status to the tie subject (if current state is tied `Chat`, otherwise do nothing)
+	fn status_broadcast(&mut self) {
+		if let AppState::Chat(chat) = &self.state {
+			if let ChatState::Tied(_) = chat.state {
+				self.client_do(|c| c.set_status(&self.status));
+			}
+		}
+	}
+	/// Add text to App Chat's messages (if current state is `Chat`, otherwise do nothing).
+	/// Returns whether it's the echo of user's own message
+	fn chat_messages_add(&mut self, sender: &str, text: &str, time: Option<DateTime<Utc>>, ttl: Option<Duration>) -> bool {
 		match &self.state {
-			AppState::Chat(chat) => {
-				chat.messages_add(msg);
+			AppState::Chat(_) => {
+				let mut message = ChatMessage::received(sender, text, time, ttl);
+				// Only a message shown as pending is echoed back, as the subject may call themselves anything.
+				// The oldest one is delivered, should the same text be sent twice
+				let pending = self
+					.chat_messages
+					.iter_mut()
+					.rev()
+					.find(|m| m.state == MessageState::Pending && m.sender == message.sender && m.text == message.text);
+				let own = pending.is_some();
+				match pending {
+					Some(pending) => {
+						message.state = MessageState::Delivered;
+						// Time is up when it was meant to be since sending, not since the echo
+						message.expires = pending.expires;
+						*pending = message
+					}
+					None => self.chat_messages_insert(message),
+				}
+				self.chat_messages_save();
+				own
 			}
-			_ => { /* TODO: Maybe panic? */ }
+			_ => false,
+		}
+	}
+	/// Remove ephemeral messages whose time is up
+	fn chat_messages_expire(&mut self) {
+		let now = Utc::now();
+		let expired = |m: &ChatMessage| m.expires.is_some_and(|expires| expires <= now);
+		// Keep the messages being read in place
+		self.scroll -= self.chat_messages.iter().take(self.scroll).filter(|m| expired(m)).count();
+		self.chat_messages.retain(|m| !expired(m));
+	}
+	/// Show user's own message right away, until the server echoes it back
+	fn chat_messages_add_pending(&mut self, text: &str) {
+		let username = match &self.user_key {
+			Some(user_key) => user_key.username.as_str(),
+			None => "",
+		};
+		let message = ChatMessage::pending(username, text, self.ttl);
+		self.chat_messages_insert(message);
+	}
+	fn chat_messages_insert(&mut self, message: ChatMessage) {
+		self.chat_messages.insert(0, message);
+		if self.scroll > 0 {
+			// Keep the messages being read in place
+			self.scroll += 1
+		}
+	}
+	/// Save chat to history, if it's kept. Pending messages aren't saved, as they may never be delivered
+	fn chat_messages_save(&self) {
+		if let Some(history) = &self.history {
+			let delivered: Vec<&ChatMessage> = self
+				.chat_messages
+				.iter()
+				.filter(|m| m.state != MessageState::Pending && m.expires.is_none())
+				.collect();
+			// There's no place to report this to in `Chat`, so the message just stays unsaved
+			history.save(&delivered).ok();
 		}
 	}
 }
@@ -258,185 +495,458 @@ impl App {
 // I could not figure out a better workaround. I ought to though. It's unsafe. Scary. Brrrrr.
 /// The main global App instance, initialized as nullable
 static mut APP: App = App::null();
-/// The job log. It's here for... reasons
-static mut JOB_LOG: Vec<String> = Vec::new();
-/// All messages of the current chat
-static mut CHAT_MESSAGES: Vec<String> = Vec::new();
+
+/// Whether anything has panicked, so that the app is to be left as soon as possible
+static PANICKED: AtomicBool = AtomicBool::new(false);
 
 #[allow(dead_code)]
 #[cfg(debug_assertions)]
 unsafe fn print_type_of<T>(_: &T) {
-	APP.job_log_add(&format!("{}", std::any::type_name::<T>()))
+	APP.job_log_add(LogLevel::Info, std::any::type_name::<T>())
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+	let config = match config::Config::load() {
+		Ok(ok) => ok,
+		Err(err) => {
+			strings::init(None);
+			eprintln!("{}\n{}", CONFIG_ERROR, err);
+			process::exit(1);
+		}
+	};
+	strings::init(config.language.as_deref());
+	let args = match cli::parse_args(std::env::args().skip(1)) {
+		Ok(ok) => ok,
+		Err(err) => {
+			eprintln!("{}\n{}", err, CLI_USAGE);
+			process::exit(2);
+		}
+	};
+	let trace = match &args.trace {
+		Some(path) => match husky::Trace::create(path) {
+			Ok(trace) => Some(trace),
+			Err(err) => {
+				eprintln!("{} {}: {}", CLI_TRACE_FAULT, path.display(), err);
+				process::exit(1);
+			}
+		},
+		None => None,
+	};
+	let replay = match args.mode {
+		cli::Mode::Tui => None,
+		cli::Mode::Replay { ref path, speed } => match husky::load_trace(path) {
+			Ok(records) => Some((records, speed)),
+			Err(err) => {
+				eprintln!("{} {}: {}", CLI_REPLAY_FAULT, path.display(), err);
+				process::exit(1);
+			}
+		},
+		cli::Mode::Help => {
+			println!("{}", CLI_USAGE);
+			process::exit(0);
+		}
+		_ => unsafe {
+			APP = App::initial(config);
+			APP.trace = trace;
+			if let Err(err) = cli::run(args).await {
+				eprintln!("{}", err);
+				process::exit(1);
+			}
+			process::exit(0);
+		},
+	};
 	let orig_hook = panic::take_hook();
 	panic::set_hook(Box::new(move |panic_info| {
+		// Panic message is only readable once the terminal is back to normal
+		disable_raw_mode().ok();
+		execute!(
+			io::stdout(),
+			LeaveAlternateScreen,
+			DisableMouseCapture,
+			DisableFocusChange,
+			Show,
+			notify::TitleStack::Pop
+		)
+		.ok();
 		orig_hook(panic_info);
-		disable_raw_mode().unwrap();
-		process::exit(1);
+		match crash::report(panic_info) {
+			Ok(path) => eprintln!("{} {}", CRASH_REPORT_SAVED, path.display()),
+			Err(err) => eprintln!("{}: {}", CRASH_REPORT_FAULT, err),
+		}
+		// Panicked task is gone, but the server is still to be left in an orderly way, which is up to `main`
+		PANICKED.store(true, Ordering::SeqCst);
 	}));
 	unsafe {
 		enable_raw_mode()?;
 		let mut stdout = io::stdout();
-		execute!(stdout, EnterAlternateScreen)?;
+		execute!(stdout, notify::TitleStack::Push, EnterAlternateScreen, EnableMouseCapture, EnableFocusChange)?;
 		let backend = CrosstermBackend::new(stdout);
 		let mut terminal = Terminal::new(backend)?;
-		APP = App::initial();
-		let result = run_app(&mut terminal).await;
+		APP = App::initial(config);
+		APP.trace = trace;
+		if let Some((records, speed)) = replay {
+			// Replayed messages must never end up in real history
+			APP.config.history = false;
+			APP.replaying = true;
+			tokio::spawn(replay::run(records, speed));
+		}
+		if let (true, Some(dir), Some(retention)) = (
+			APP.config.history,
+			config::Config::history_dir(),
+			APP.config.history_retention(),
+		) {
+			history::prune(&dir, retention).ok();
+		}
+		tokio::spawn(signals_watch());
+		let result = panic::AssertUnwindSafe(run_app(&mut terminal)).catch_unwind().await;
+		disconnect().await;
+		if PANICKED.load(Ordering::SeqCst) {
+			// Terminal is already restored by the panic hook
+			process::exit(1);
+		}
 		disable_raw_mode()?;
-		execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+		execute!(
+			terminal.backend_mut(),
+			LeaveAlternateScreen,
+			DisableMouseCapture,
+			DisableFocusChange,
+			notify::TitleStack::Pop
+		)?;
 		terminal.show_cursor()?;
-		if let Err(err) = result {
+		if let Ok(Err(err)) = result {
 			println!("{}\n{:?}", FATAL_RUNTIME_ERROR, err)
 		}
 		process::exit(0);
 	}
 }
 
+/// How long to wait for the server to be left in an orderly way on exit
+const DISCONNECT_TIMEOUT: time::Duration = time::Duration::from_secs(3);
+
+/// Ask app to exit once the process is told to terminate
+async fn signals_watch() {
+	#[cfg(unix)]
+	{
+		use tokio::signal::unix::{signal, SignalKind};
+		let (Ok(mut terminate), Ok(mut hangup), Ok(mut interrupt)) = (
+			signal(SignalKind::terminate()),
+			signal(SignalKind::hangup()),
+			signal(SignalKind::interrupt()),
+		) else {
+			return;
+		};
+		tokio::select! {
+			_ = terminate.recv() => (),
+			_ = hangup.recv() => (),
+			_ = interrupt.recv() => (),
+		}
+	}
+	#[cfg(not(unix))]
+	if tokio::signal::ctrl_c().await.is_err() {
+		return;
+	}
+	unsafe { APP.requested_exit = true }
+}
+
+/// Leave server and close socket, giving up if it takes too long
+async unsafe fn disconnect() {
+	if let Some(client) = APP.client.take() {
+		tokio::time::timeout(DISCONNECT_TIMEOUT, client.close()).await.ok();
+	}
+}
+
 /// App's lifecycle loop
-async unsafe fn run_app<B: Backend>(terminal: &mut Terminal<B>) -> io::Result<()> {
+async unsafe fn run_app<B: Backend + io::Write>(terminal: &mut Terminal<B>) -> io::Result<()> {
 	async unsafe fn perform() {
 		loop {
-			let event = event::read();
+			// Reading blocks, so other tasks must be moved off this thread not to be starved
+			let event = tokio::task::block_in_place(event::read);
 			if event.is_err() {
 				return;
 			}
-			if let Event::Key(key) = event.unwrap() {
-				match key.modifiers {
-					KeyModifiers::CONTROL => {
-						if key.code == KeyCode::Char('c') {
-							APP.requested_exit = true;
-							return;
+			let key = match event.unwrap() {
+				Event::Key(key) if key.kind != KeyEventKind::Release => key,
+				Event::Mouse(mouse) => {
+					mouse_perform(mouse).await;
+					continue;
+				}
+				Event::FocusGained => {
+					APP.focused = true;
+					APP.unread = 0;
+					continue;
+				}
+				Event::FocusLost => {
+					APP.focused = false;
+					continue;
+				}
+				_ => continue,
+			};
+			APP.last_input = Some(time::Instant::now());
+			APP.status_presence_set(Presence::Online);
+			if APP.help_shown {
+				APP.help_shown = false;
+				continue;
+			}
+			match APP.keymap.action(&key, APP.input_focus != 0) {
+				Some(Action::Quit) => {
+					APP.requested_exit = true;
+					return;
+				}
+				Some(Action::Help) => APP.help_shown = true,
+				Some(Action::SetStatus) => {
+					if let AppState::Chat(_) = APP.state {
+						status_edit_begin()
+					}
+				}
+				Some(Action::Export) => {
+					if let AppState::Chat(_) = APP.state {
+						export_transcript(APP.config.export_format)
+					}
+				}
+				Some(Action::SaveLog) => {
+					if let AppState::Job(_) = APP.state {
+						job_log_save()
+					}
+				}
+				Some(Action::ToggleInvisible) => {
+					if let AppState::Chat(_) = APP.state {
+						APP.show_invisible = !APP.show_invisible
+					}
+				}
+				Some(Action::Complete) => {
+					if APP.input_focus == 3 && !APP.status_editing {
+						if let Some(completed) = commands::complete(&APP.inputs[2]) {
+							APP.inputs[2] = completed
 						}
 					}
-					_ => match key.code {
-						KeyCode::F(9) => {
-							APP.requested_exit = true;
-							return;
+				}
+				Some(Action::Compose) => {
+					if let AppState::Chat(_) = APP.state {
+						APP.input_focus = 3;
+						APP.requested_editor = true;
+						// The editor reads keys itself, so keep off the terminal until it's done
+						while APP.requested_editor {
+							tokio::time::sleep(time::Duration::from_millis(10)).await
 						}
-						KeyCode::Up => {
-							if APP.input_focus <= 0 {
-								APP.input_focus = APP.max_input_focus
-							} else {
-								APP.input_focus -= 1
-							}
+					}
+				}
+				Some(Action::Cancel) => {
+					if APP.status_editing {
+						status_edit_end()
+					}
+				}
+				Some(Action::FocusPrev) => {
+					if APP.status_editing {
+						status_edit_end()
+					}
+					if APP.input_focus == 0 {
+						APP.input_focus = APP.max_input_focus
+					} else {
+						APP.input_focus -= 1
+					}
+				}
+				Some(Action::FocusNext) => {
+					if APP.status_editing {
+						status_edit_end()
+					}
+					if APP.input_focus >= APP.max_input_focus {
+						APP.input_focus = 0
+					} else {
+						APP.input_focus += 1
+					}
+				}
+				Some(Action::ScrollUp) => scroll_up(),
+				Some(Action::ScrollDown) => scroll_down(),
+				Some(Action::Untie) => {
+					if let AppState::Chat(Chat {
+						state: ChatState::Tied(_),
+					}) = APP.state
+					{
+						untie().await
+					}
+				}
+				Some(Action::Submit) => {
+					if APP.state == AppState::Auth && APP.input_focus == 1 {
+						APP.requested_job = 1
+					} else if let AppState::Chat(_) = &APP.state {
+						match &APP.input_focus {
+							0 => leave_chat().await,
+							1 => APP.requested_job = 2,
+							3 if APP.status_editing => status_edit_apply(),
+							3 => submit_message().await,
+							_ => (),
 						}
-						KeyCode::Down => {
-							if APP.input_focus >= APP.max_input_focus {
-								APP.input_focus = 0
-							} else {
-								APP.input_focus += 1
+					} else if let AppState::Job(job) = &APP.state {
+						match &job.state {
+							JobState::InProgress(switch) if switch.is_some() => {
+								job_abort(switch.clone().unwrap());
 							}
+							JobState::Ok(switch) => {
+								set_state_using_switch(switch.clone());
+								continue;
+							}
+							JobState::Err(switch) => {
+								set_state_using_switch(switch.clone());
+								continue;
+							}
+							_ => (),
 						}
-						KeyCode::Enter => {
-							if APP.state == AppState::Auth && APP.input_focus == 1 {
-								APP.requested_job = 1
-							} else if let AppState::Chat(chat) = &APP.state {
-								match &APP.input_focus {
-									0 => {
-										if let ChatState::Untied = chat.state {
-											set_state(AppState::Auth)
-										} else {
-											untie().await
-										}
-									}
-									1 => APP.requested_job = 2,
-									3 => send_message().await,
-									_ => (),
-								}
-							} else if let AppState::Job(job) = &APP.state {
-								match &job.state {
-									JobState::InProgress(switch) if switch.is_some() => {
-										// FIXME:
-										// Undef behavior in case we weren't tying
-										APP.sending_queue_add(RXTX_UNTIE_FLAG.to_string());
-										set_state_using_switch(switch.clone().unwrap());
-									}
-									JobState::Ok(switch) => {
-										set_state_using_switch(switch.clone());
-										continue;
-									}
-									JobState::Err(switch) => {
-										set_state_using_switch(switch.clone());
-										continue;
-									}
-									_ => (),
-								}
-							};
-						}
-						a @ _ => {
-							if APP.input_focus != 0 {
-								match a {
-									KeyCode::Char(c) => {
-										APP.inputs[(APP.input_focus - 1) as usize].push(c)
-									}
-									KeyCode::Backspace => {
-										(APP.inputs[(APP.input_focus - 1) as usize].pop());
-									}
-									_ => (),
-								}
+					};
+				}
+				None => {
+					if APP.input_focus != 0 {
+						match key.code {
+							KeyCode::Char(c) if !key.modifiers.contains(KeyModifiers::CONTROL) => {
+								APP.inputs[(APP.input_focus - 1) as usize].push(c)
+							}
+							KeyCode::Backspace => {
+								(APP.inputs[(APP.input_focus - 1) as usize].pop());
 							}
+							_ => (),
 						}
-					},
+					}
 				}
 			}
 		}
 	}
 	tokio::spawn(perform());
+	// Unread count the terminal title shows
+	let mut title_unread = 0;
 	// TODO:
 	// Is it ok that the interface is being updated all the time? I really don't know
 	loop {
 		thread::sleep(time::Duration::from_millis(50));
-		if APP.requested_exit {
+		if APP.requested_exit || PANICKED.load(Ordering::SeqCst) {
 			return Ok(());
 		}
+		if let Some(last_input) = APP.last_input {
+			let timeout = APP.config.idle_timeout;
+			if timeout != 0 && last_input.elapsed() >= time::Duration::from_secs(timeout) {
+				APP.status_presence_set(Presence::Away);
+			}
+		}
+		if APP.replaying {
+			// There's no server to run jobs against
+			APP.requested_job = 0;
+		}
 		match &APP.requested_job {
 			1 => {
 				APP.requested_job = 0;
-				tokio::spawn(start_auth_job());
+				run_job(start_auth_job());
 				continue;
 			}
 			2 => {
 				APP.requested_job = 0;
-				tokio::spawn(start_tie_job());
+				run_job(start_tie_job());
 				continue;
 			}
 			_ => (),
 		}
-		match APP.state {
-			AppState::Auth => {
-				terminal.draw(|f| auth_ui(f))?;
-			}
-			AppState::Chat(_) => {
-				terminal.draw(|f| chat_ui(f))?;
-			}
-			AppState::Job(_) => {
-				terminal.draw(|f| job_ui(f))?;
+		APP.chat_messages_expire();
+		if APP.requested_editor {
+			compose(terminal)?;
+			APP.requested_editor = false;
+		}
+		// Written here so that it doesn't end up in the middle of a frame
+		if let Some(notice) = APP.notice.take() {
+			notify::alert(terminal.backend_mut(), APP.config.notify, &notice)?;
+		}
+		if APP.unread != title_unread {
+			title_unread = APP.unread;
+			execute!(terminal.backend_mut(), SetTitle(notify::title(title_unread)))?;
+		}
+		terminal.draw(|f| ui::draw(f, &APP))?;
+	}
+}
+
+/// Suspend the interface to let the user write message in external editor
+unsafe fn compose<B: Backend + io::Write>(terminal: &mut Terminal<B>) -> io::Result<()> {
+	disable_raw_mode()?;
+	execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture, DisableFocusChange)?;
+	let composed = editor::compose(&editor::command(), &APP.inputs[2]);
+	enable_raw_mode()?;
+	execute!(terminal.backend_mut(), EnterAlternateScreen, EnableMouseCapture, EnableFocusChange)?;
+	terminal.clear()?;
+	// Writing could take a while, but the user is clearly not away
+	APP.last_input = Some(time::Instant::now());
+	match composed {
+		Ok(text) => APP.inputs[2] = text,
+		Err(err) => APP.chat_notice = Some(format!("{}: {}", EDITOR_FAULT, err)),
+	}
+	Ok(())
+}
+
+/// Act on mouse click or wheel
+async unsafe fn mouse_perform(mouse: MouseEvent) {
+	match mouse.kind {
+		MouseEventKind::ScrollUp => scroll_up(),
+		MouseEventKind::ScrollDown => scroll_down(),
+		MouseEventKind::Down(MouseButton::Left) => {
+			if APP.help_shown {
+				APP.help_shown = false;
+			} else if let AppState::Chat(_) = APP.state {
+				let (width, height) = match crossterm::terminal::size() {
+					Ok(size) => size,
+					Err(_) => return,
+				};
+				match ui::chat_target(&APP, Rect::new(0, 0, width, height), mouse.column, mouse.row) {
+					Some(ChatTarget::HeaderHint) => leave_chat().await,
+					Some(ChatTarget::Header) => focus_set(0),
+					Some(ChatTarget::Subject) => focus_set(1),
+					Some(ChatTarget::EncryptionKey) => focus_set(2),
+					Some(ChatTarget::NewMessage) => focus_set(3),
+					Some(ChatTarget::Messages) | None => (),
+				}
 			}
 		}
+		// Merely moving the mouse around doesn't make the user online
+		_ => return,
+	}
+	APP.last_input = Some(time::Instant::now());
+	APP.status_presence_set(Presence::Online);
+}
+
+/// Focus input, leaving status editing if it's the message input that loses focus
+unsafe fn focus_set(focus: u8) {
+	if APP.input_focus != focus && APP.status_editing {
+		status_edit_end()
+	}
+	APP.input_focus = focus
+}
+
+/// Scroll towards older messages in chat, or towards the beginning of the log in jobs
+unsafe fn scroll_up() {
+	match APP.state {
+		AppState::Chat(_) => APP.scroll = (APP.scroll + 1).min(APP.chat_messages.len().saturating_sub(1)),
+		AppState::Job(_) => APP.scroll = APP.scroll.saturating_sub(1),
+		AppState::Auth => (),
+	}
+}
+
+/// Scroll towards newer messages in chat, or towards the end of the log in jobs
+unsafe fn scroll_down() {
+	match APP.state {
+		AppState::Chat(_) => APP.scroll = APP.scroll.saturating_sub(1),
+		AppState::Job(_) => APP.scroll = (APP.scroll + 1).min(APP.job_log.len().saturating_sub(1)),
+		AppState::Auth => (),
 	}
 }
 
 /// Switch App's state to a corresponding one and reset all associated variables
 unsafe fn set_state(to: AppState) {
 	match &to {
-		AppState::Chat(chat) => {
-			APP.max_input_focus = if let ChatState::Tied(_) = chat.state {
-				APP.input_focus = 2;
-				3
-			} else {
-				APP.input_focus = 1;
-				1
-			};
+		AppState::Chat(_) => {
+			// Message block is reachable even when untied to accept commands
+			APP.max_input_focus = 3;
 			APP.input_focus = 1;
-			CHAT_MESSAGES = Vec::new();
-			JOB_LOG = Vec::new();
+			APP.chat_messages = Vec::new();
+			APP.job_log = Vec::new();
+			APP.history = None;
 		}
 		AppState::Auth => {
-			if APP.writer_exists {
-				APP.sending_queue_add(TX_DROPME_FLAG.to_string())
+			if let Some(client) = APP.client.take() {
+				client.drop_me().ok();
 			}
 			// if APP.socket_handles.is_some() {
 			// 	APP.socket_handles.as_ref().unwrap().0.abort();
@@ -444,7 +954,7 @@ unsafe fn set_state(to: AppState) {
 			// }
 			APP.max_input_focus = 1;
 			APP.input_focus = 0;
-			JOB_LOG = Vec::new();
+			APP.job_log = Vec::new();
 		}
 		AppState::Job(_) => {
 			APP.max_input_focus = 1;
@@ -452,6 +962,11 @@ unsafe fn set_state(to: AppState) {
 		}
 	}
 	APP.inputs = [String::new(), String::new(), String::new()];
+	APP.status_editing = false;
+	APP.chat_notice = None;
+	APP.transfer = None;
+	APP.scroll = 0;
+	APP.ttl = None;
 	APP.state = to;
 }
 
@@ -459,279 +974,526 @@ unsafe fn set_state(to: AppState) {
 unsafe fn set_state_using_switch(to: JobSwitchAppState) {
 	match &to {
 		JobSwitchAppState::Chat(chat) => {
-			APP.max_input_focus = if let ChatState::Tied(_) = chat.state {
-				APP.input_focus = 2;
-				3
+			APP.max_input_focus = 3;
+			APP.input_focus = if let ChatState::Tied(_) = chat.state {
+				2
 			} else {
-				APP.input_focus = 1;
 				1
 			};
-			CHAT_MESSAGES = Vec::new();
-			JOB_LOG = Vec::new();
+			// History of a new tie is restored once it's acknowledged
+			if let ChatState::Untied = chat.state {
+				APP.chat_messages = Vec::new();
+				APP.history = None;
+			}
+			APP.job_log = Vec::new();
 			APP.state = AppState::Chat(chat.to_owned());
 		}
 		JobSwitchAppState::Auth => {
-			if APP.writer_exists {
-				APP.sending_queue_add(TX_DROPME_FLAG.to_string())
+			if let Some(client) = APP.client.take() {
+				client.drop_me().ok();
 			}
 			APP.max_input_focus = 1;
 			APP.input_focus = 0;
-			JOB_LOG = Vec::new();
+			APP.job_log = Vec::new();
 			APP.state = AppState::Auth;
 		}
 	}
 	APP.inputs = [String::new(), String::new(), String::new()];
+	APP.status_editing = false;
+	APP.chat_notice = None;
+	APP.transfer = None;
+	APP.scroll = 0;
+	APP.ttl = None;
 }
 
-/// Daemon for acting on every incoming message
-async unsafe fn read_ws(
-	with: futures_util::stream::SplitStream<
-		tokio_tungstenite::WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>,
-	>,
-) {
-	with.for_each(|message| async {
-		match message {
-			Ok(ok) => match ok {
-				Message::Text(txt) => {
-					let chars = txt.chars();
-					let flag = chars.clone().collect::<Vec<char>>()[0] as char;
-					match flag {
-						RX_AUTH_OK_FLAG => {
-							if let AppState::Job(job) = &APP.state {
-								if job.title == AUTH_JOB {
-									APP.job_log_add(JOB_SUCCESS);
-									APP.job_progress_set(100);
-									APP.job_state_set(
-										JobState::Ok(JobSwitchAppState::Chat(Chat::default())),
-										false,
-									);
-								} else {
-									// TODO:
-									// Panic?
-								}
-							}
-						}
-						RX_AUTH_FAULT_FLAG => {
-							if let AppState::Job(job) = &APP.state {
-								if job.title == AUTH_JOB {
-									APP.job_log_add(AUTH_JOB_CONNECT_AUTH_FAULT);
-									APP.job_state_set(JobState::Err(JobSwitchAppState::Auth), false)
-								} else {
-									// TODO:
-									// Panic?
-								}
-							}
-						}
-						RX_AUTH_FAULT_OVERAUTH_FLAG => {
-							if let AppState::Job(job) = &APP.state {
-								if job.title == AUTH_JOB {
-									APP.job_log_add(AUTH_JOB_CONNECT_AUTH_FAULT_OVERAUTH);
-									APP.job_state_set(JobState::Err(JobSwitchAppState::Auth), false)
-								} else {
-									// TODO:
-									// Panic?
-								}
-							}
-						}
-						RX_TIE_OK_FLAG => {
-							if let AppState::Job(job) = &APP.state {
-								if job.title == TIE_JOB {
-									let subject = job.data[0].to_string();
-									APP.job_log_add(JOB_SUCCESS);
-									APP.job_progress_set(100);
-									APP.job_state_set(
-										JobState::Ok(JobSwitchAppState::Chat(Chat::with_subject(
-											subject,
-										))),
-										false,
-									);
-								} else {
-									// TODO:
-									// Panic?
-								}
-							}
-						}
-						RX_TIE_OK_WAIT_FLAG => {
-							if let AppState::Job(job) = &APP.state {
-								if job.title == TIE_JOB {
-									APP.job_log_add(TIE_JOB_AWAITING);
-									APP.job_progress_set(50);
-								} else {
-									// TODO:
-									// Panic?
-								}
-							}
-						}
-						RX_TIE_FAULT_NOUSER_FLAG => {
-							if let AppState::Job(job) = &APP.state {
-								if job.title == TIE_JOB {
-									APP.job_log_add(TIE_JOB_FAULT_NOUSER);
-									APP.job_state_set(
-										JobState::Err(JobSwitchAppState::Chat(Chat::default())),
-										false,
-									)
-								} else {
-									// TODO:
-									// Panic?
-								}
-							}
-						}
-						RX_TIE_FAULT_SELFTIE_FLAG => {
-							if let AppState::Job(job) = &APP.state {
-								if job.title == TIE_JOB {
-									APP.job_log_add(TIE_JOB_FAULT_SELFTIE);
-									APP.job_state_set(
-										JobState::Err(JobSwitchAppState::Chat(Chat::default())),
-										false,
-									)
-								} else {
-									// TODO:
-									// Panic?
-								}
-							}
-						}
-						RX_TIE_FAULT_OVERTIE_FLAG => {
-							if let AppState::Job(job) = &APP.state {
-								if job.title == TIE_JOB {
-									APP.job_log_add(TIE_JOB_FAULT_OVERTIE);
-									APP.job_state_set(
-										JobState::Err(JobSwitchAppState::Chat(Chat::default())),
-										false,
-									);
-									APP.sending_queue_add(RXTX_UNTIE_FLAG.to_string());
-								} else {
-									// TODO:
-									// Panic?
-								}
-							}
-						}
-						RXTX_UNTIE_FLAG => {
-							if let AppState::Chat(chat) = &APP.state {
-								if let ChatState::Tied(_) = chat.state {
-									APP.job_state_set(
-										JobState::Err(JobSwitchAppState::Chat(Chat::default())),
-										true,
-									);
-									APP.job_log_add(TIE_BROKEN);
-								} else {
-									// TODO:
-									// Panic?
-								}
-							}
-						}
-						RXTX_MESSAGE_FLAG => {
-							if let AppState::Chat(chat) = &APP.state {
-								if let ChatState::Tied(_) = chat.state {
-									let body: String = chars.skip(1).collect();
-									APP.chat_messages_add(&body);
-								} else {
-									// TODO:
-									// Panic?
-								}
-							}
-						}
-						RXTX_FAULT_FLAG => {
-							APP.job_state_set(JobState::Err(JobSwitchAppState::Auth), true);
-							APP.job_log_add(RX_GENERAL_ERROR);
-						}
-						RXTX_OK_FLAG => (),
-						_ => APP.job_log_add(&txt),
-					}
-				}
-				_ => (),
-			},
+/// Load message history of the tie with `subject` (if enabled in config, otherwise forget current history)
+unsafe fn history_restore(subject: &str) {
+	APP.history = None;
+	APP.chat_messages = Vec::new();
+	if !APP.config.history {
+		return;
+	}
+	let (dir, user_key) = match (config::Config::history_dir(), &APP.user_key) {
+		(Some(dir), Some(user_key)) => (dir, user_key),
+		_ => return,
+	};
+	let passphrase = match APP.config.history_passphrase() {
+		Some(passphrase) => passphrase,
+		None => {
+			APP.job_log_add(LogLevel::Warning, &format!("{} {}", TIE_JOB_HISTORY_FAULT, HISTORY_NO_PASSPHRASE));
+			return;
+		}
+	};
+	match history::History::open::<ChatMessage>(&dir, &user_key.username, subject, &passphrase) {
+		Ok((history, mut messages)) => {
+			// Retention too long to count is as good as forever
+			let since = APP
+				.config
+				.history_retention()
+				.and_then(|retention| chrono::Duration::from_std(retention).ok())
+				.and_then(|retention| Utc::now().checked_sub_signed(retention));
+			if let Some(since) = since {
+				messages.retain(|m| m.time >= since);
+			}
+			APP.chat_messages = messages;
+			APP.history = Some(history);
+		}
+		// History which can't be decrypted is left untouched rather than overwritten
+		Err(err) => APP.job_log_add(LogLevel::Warning, &format!("{} {}", TIE_JOB_HISTORY_FAULT, err)),
+	}
+}
+
+/// Daemon for acting on every incoming event
+async unsafe fn read_events(mut events: Events) {
+	while let Some(event) = events.next().await {
+		match event {
+			Ok(event) => handle_event(event),
+			Err(husky::Error::Send) => {
+				APP.job_log_add(LogLevel::Fault, &AUTH_JOB_CONNECT_FAULT);
+				APP.job_state_set(JobState::Err(JobSwitchAppState::Auth), false);
+			}
 			Err(_) => {
 				APP.job_state_set(JobState::Err(JobSwitchAppState::Auth), false);
-				APP.job_log_add(MESSAGE_CORRUPTED_ERROR)
+				APP.job_log_add(LogLevel::Fault, &MESSAGE_CORRUPTED_ERROR)
 			}
 		}
-	})
-	.await;
+	}
 	// TODO:
 	// This is from an earlier commit, is it ok?
 	if let AppState::Chat(_) = APP.state {
 		APP.job_state_set(JobState::Err(JobSwitchAppState::Auth), true);
-		APP.job_log_add(CONNECTION_DROPPED_ERROR)
+		APP.job_log_add(LogLevel::Fault, &CONNECTION_DROPPED_ERROR)
 	}
 }
 
-/// Daemon for sending messages from queue
-#[tokio::main]
-async unsafe fn write_ws(
-	mut with: futures_util::stream::SplitSink<
-		WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>,
-		Message,
-	>,
-) {
-	APP.writer_exists = true;
-	'outer: loop {
-		// TODO:
-		// Is sleeping a good idea?
-		thread::sleep(time::Duration::from_millis(100));
-		let mut sent: u8 = 0;
-		let app_sent = APP.sending_queue_sent;
-		// Fuck i'm an idiot
-		if app_sent >= 10 && APP.sending_queue.len() < 10 {
-			APP.job_log_add(&format!("1.1"));
-			APP.sending_queue_sent = 0;
-			APP.job_log_add(&format!("1.2"));
-			continue 'outer;
-		}
-		for i in &APP.sending_queue {
-			if sent < app_sent {
-				sent += 1;
-				continue;
+/// Act on a single event received from server
+unsafe fn handle_event(event: husky::Event) {
+	match event {
+		husky::Event::AuthOk => {
+			if let AppState::Job(job) = &APP.state {
+				if job.title == AUTH_JOB {
+					APP.job_log_add(LogLevel::Success, &JOB_SUCCESS);
+					APP.job_progress_set(100);
+					APP.job_state_set(
+						JobState::Ok(JobSwitchAppState::Chat(Chat::default())),
+						false,
+					);
+				} else {
+					// TODO:
+					// Panic?
+				}
 			}
-			// DEBUG:
-			// APP.job_log_add(&format!("2. s:{} as:{}, i:{}, len:{}", sent, app_sent, i));
-			if with.send(Message::Text(i.to_string())).await.is_err() {
-				APP.job_log_add(AUTH_JOB_CONNECT_FAULT);
-				APP.job_state_set(JobState::Err(JobSwitchAppState::Auth), false);
+		}
+		husky::Event::AuthFault => {
+			if let AppState::Job(job) = &APP.state {
+				if job.title == AUTH_JOB {
+					APP.job_log_add(LogLevel::Fault, &AUTH_JOB_CONNECT_AUTH_FAULT);
+					APP.job_state_set(JobState::Err(JobSwitchAppState::Auth), false)
+				} else {
+					// TODO:
+					// Panic?
+				}
 			}
-			if i == &TX_DROPME_FLAG.to_string() {
-				APP.sending_queue = Vec::new();
-				APP.sending_queue_sent = 0;
-				break 'outer;
+		}
+		husky::Event::AuthFaultOverauth => {
+			if let AppState::Job(job) = &APP.state {
+				if job.title == AUTH_JOB {
+					APP.job_log_add(LogLevel::Fault, &AUTH_JOB_CONNECT_AUTH_FAULT_OVERAUTH);
+					APP.job_state_set(JobState::Err(JobSwitchAppState::Auth), false)
+				} else {
+					// TODO:
+					// Panic?
+				}
+			}
+		}
+		husky::Event::TieOk => {
+			if let AppState::Job(job) = &APP.state {
+				if job.title == TIE_JOB {
+					let subject = job.data[0].to_string();
+					APP.client_do(|c| c.set_status(&APP.status));
+					APP.job_log_add(LogLevel::Success, &JOB_SUCCESS);
+					history_restore(&subject);
+					APP.job_progress_set(100);
+					APP.job_state_set(
+						JobState::Ok(JobSwitchAppState::Chat(Chat::with_subject(
+							subject,
+						))),
+						false,
+					);
+				} else {
+					// TODO:
+					// Panic?
+				}
+			}
+		}
+		husky::Event::TieWait => {
+			if let AppState::Job(job) = &APP.state {
+				if job.title == TIE_JOB {
+					// The subject may take their time, so the job is no longer timed
+					APP.job_waiting = true;
+					APP.job_log_add(LogLevel::Info, &TIE_JOB_AWAITING);
+					APP.job_progress_set(50);
+				} else {
+					// TODO:
+					// Panic?
+				}
+			}
+		}
+		husky::Event::TieFaultNoUser => {
+			if let AppState::Job(job) = &APP.state {
+				if job.title == TIE_JOB {
+					APP.job_log_add(LogLevel::Fault, &TIE_JOB_FAULT_NOUSER);
+					APP.job_state_set(
+						JobState::Err(JobSwitchAppState::Chat(Chat::default())),
+						false,
+					)
+				} else {
+					// TODO:
+					// Panic?
+				}
+			}
+		}
+		husky::Event::TieFaultSelfTie => {
+			if let AppState::Job(job) = &APP.state {
+				if job.title == TIE_JOB {
+					APP.job_log_add(LogLevel::Fault, &TIE_JOB_FAULT_SELFTIE);
+					APP.job_state_set(
+						JobState::Err(JobSwitchAppState::Chat(Chat::default())),
+						false,
+					)
+				} else {
+					// TODO:
+					// Panic?
+				}
+			}
+		}
+		husky::Event::TieFaultOverTie => {
+			if let AppState::Job(job) = &APP.state {
+				if job.title == TIE_JOB {
+					APP.job_log_add(LogLevel::Fault, &TIE_JOB_FAULT_OVERTIE);
+					APP.job_state_set(
+						JobState::Err(JobSwitchAppState::Chat(Chat::default())),
+						false,
+					);
+					APP.client_do(|c| c.untie());
+				} else {
+					// TODO:
+					// Panic?
+				}
 			}
-			APP.sending_queue_sent += 1;
+		}
+		husky::Event::Untie => {
+			if let AppState::Chat(chat) = &APP.state {
+				if let ChatState::Tied(_) = chat.state {
+					APP.transfer = None;
+					APP.job_state_set(
+						JobState::Err(JobSwitchAppState::Chat(Chat::default())),
+						true,
+					);
+					APP.job_log_add(LogLevel::Fault, &TIE_BROKEN);
+				} else {
+					// TODO:
+					// Panic?
+				}
+			}
+		}
+		husky::Event::Message { sender, text, time, ttl } => {
+			if let AppState::Chat(chat) = &APP.state {
+				if let ChatState::Tied(_) = chat.state {
+					let own = APP.chat_messages_add(&sender, &text, time, ttl);
+					if !APP.focused && !own {
+						message_notify(sender, text)
+					}
+				} else {
+					// TODO:
+					// Panic?
+				}
+			}
+		}
+		husky::Event::Status(status) => APP.subject_status = Some(status),
+		husky::Event::FileOffer(offer) => files::on_offer(offer),
+		husky::Event::FileAccept => files::on_accept(),
+		husky::Event::FileDecline => files::on_decline(),
+		husky::Event::FileChunk(chunk) => files::on_chunk(&chunk),
+		husky::Event::FileAck => files::on_ack(),
+		husky::Event::FileDone { verified } => files::on_done(verified),
+		husky::Event::Fault => {
+			APP.job_state_set(JobState::Err(JobSwitchAppState::Auth), true);
+			APP.job_log_add(LogLevel::Fault, &RX_GENERAL_ERROR);
+		}
+		husky::Event::Ok => (),
+		husky::Event::Unknown(txt) => APP.job_log_add(LogLevel::Warning, &husky::sanitize(&txt, false).0),
+	}
+}
+
+/// Count message arrived while the terminal is unfocused and let the user know about it
+unsafe fn message_notify(sender: String, text: String) {
+	APP.unread += 1;
+	let notice = notify::Notice { sender, text };
+	if let (Some(command), false) = (&APP.config.notify_command, APP.replaying) {
+		if notify::hook(command, &notice).is_err() {
+			APP.chat_notice = Some(NOTIFY_HOOK_FAULT.to_string())
+		}
+	}
+	APP.notice = Some(notice);
+}
+
+/// Act on message input: either run a command or send the message
+async unsafe fn submit_message() {
+	match commands::parse(&APP.inputs[2]) {
+		None => {
+			if let AppState::Chat(Chat {
+				state: ChatState::Tied(_),
+				..
+			}) = APP.state
+			{
+				send_message().await
+			} else {
+				APP.chat_notice = Some(COMMAND_FAULT_UNTIED.to_string())
+			}
+		}
+		Some(Ok(command)) => {
+			APP.inputs[2] = String::new();
+			APP.chat_notice = None;
+			run_command(command).await
+		}
+		Some(Err(err)) => {
+			APP.chat_notice = Some(match err {
+				commands::CommandError::Unknown(name) => format!("{} {}", COMMAND_UNKNOWN, name),
+				commands::CommandError::MissingArgument(hint) => {
+					format!("{}:{}", COMMAND_MISSING_ARGUMENT, hint)
+				}
+				commands::CommandError::BadArgument(arg) => format!("{}: {}", COMMAND_BAD_ARGUMENT, arg),
+			})
 		}
 	}
-	APP.writer_exists = false;
 }
 
-/// Make new connection and return a socket
-async unsafe fn ws_connect() -> Result<WebSocketStream<MaybeTlsStream<tokio::net::TcpStream>>, ()> {
-	let url =
-		url::Url::parse(&format!("ws://{}:{}", APP.server.root_url, APP.server.port)).unwrap();
-	let connection = connect_async(url).await;
-	if connection.is_err() {
-		return Err(());
+/// Perform action requested with a command
+async unsafe fn run_command(command: commands::Command) {
+	let tied = matches!(
+		APP.state,
+		AppState::Chat(Chat {
+			state: ChatState::Tied(_),
+			..
+		})
+	);
+	match command {
+		commands::Command::Tie(subject) => {
+			APP.inputs[0] = subject;
+			APP.requested_job = 2
+		}
+		commands::Command::Untie if tied => untie().await,
+		commands::Command::Untie => APP.chat_notice = Some(COMMAND_FAULT_UNTIED.to_string()),
+		commands::Command::Logout => set_state(AppState::Auth),
+		commands::Command::Quit => APP.requested_exit = true,
+		commands::Command::Clear => {
+			APP.chat_messages = Vec::new();
+			APP.scroll = 0;
+			if let Some(history) = &APP.history {
+				history.save(&APP.chat_messages).ok();
+			}
+		}
+		commands::Command::Export(format) => {
+			export_transcript(format.unwrap_or(APP.config.export_format))
+		}
+		commands::Command::Status(message) => {
+			APP.status.message = message;
+			APP.status_broadcast();
+		}
+		commands::Command::File(path) if tied => files::offer(&path),
+		commands::Command::Accept if tied => files::accept(),
+		commands::Command::Decline if tied => files::decline(),
+		commands::Command::Ttl(ttl) if tied => {
+			APP.ttl = ttl;
+			APP.chat_notice = Some(match ttl {
+				Some(ttl) => format!("{} {}", TTL_SET, ui::countdown(ttl)),
+				None => TTL_OFF.to_string(),
+			})
+		}
+		commands::Command::File(_)
+		| commands::Command::Accept
+		| commands::Command::Decline
+		| commands::Command::Ttl(_) => {
+			APP.chat_notice = Some(COMMAND_FAULT_UNTIED.to_string())
+		}
+		commands::Command::Help => {
+			APP.chat_notice = Some(format!("{}: {}", COMMAND_HELP, commands::help()))
+		}
 	}
-	let (ws_stream, _) = connection.unwrap();
-	Ok(ws_stream)
 }
 
 /// Send message to the current tie subject
 async unsafe fn send_message() {
-	let message = APP.inputs[2].clone();
+	let message = commands::unescape(&APP.inputs[2]).to_string();
+	APP.inputs[2] = String::new();
+	APP.chat_messages_add_pending(&message);
+	APP.client_do(|c| match APP.ttl {
+		Some(ttl) => c.send_ephemeral(&message, ttl),
+		None => c.send(&message),
+	});
+}
+
+/// Put user's current status message into the message input to be edited
+unsafe fn status_edit_begin() {
+	APP.status_editing = true;
+	APP.inputs[2] = APP.status.message.clone();
+	APP.input_focus = 3;
+}
+
+/// Leave status message editing, discarding the input
+unsafe fn status_edit_end() {
+	APP.status_editing = false;
 	APP.inputs[2] = String::new();
-	APP.sending_queue_add(format!(
-		"{}{}: {}",
-		RXTX_MESSAGE_FLAG,
-		APP.user_key.as_ref().unwrap().username,
-		message
-	));
+	if APP.input_focus > APP.max_input_focus {
+		APP.input_focus = 0
+	}
+}
+
+/// Set user's status message from the message input and let the tie subject know about it
+unsafe fn status_edit_apply() {
+	APP.status.message = APP.inputs[2].trim().to_string();
+	status_edit_end();
+	APP.status_broadcast();
+}
+
+/// Save current tie's messages to a transcript file, reporting the result as chat notice
+unsafe fn export_transcript(format: export::ExportFormat) {
+	let subject = match &APP.state {
+		AppState::Chat(Chat {
+			state: ChatState::Tied(subject),
+			..
+		}) => subject.clone(),
+		_ => {
+			APP.chat_notice = Some(EXPORT_FAULT_UNTIED.to_string());
+			return;
+		}
+	};
+	let dir = APP
+		.config
+		.export_dir
+		.clone()
+		.unwrap_or_else(|| std::path::PathBuf::from("."));
+	let username = APP.user_key.as_ref().unwrap().username.clone();
+	APP.chat_notice = Some(
+		match export::export(&dir, &username, &subject, &APP.chat_messages, format) {
+			Ok(path) => format!("{} {}", EXPORT_DONE, path.display()),
+			Err(err) => format!("{}: {}", EXPORT_FAULT, err),
+		},
+	);
+}
+
+/// Untie if tied, log out otherwise
+async unsafe fn leave_chat() {
+	if let AppState::Chat(Chat {
+		state: ChatState::Untied,
+	}) = APP.state
+	{
+		set_state(AppState::Auth)
+	} else {
+		untie().await
+	}
 }
 
 /// Stop tie if exists
 async unsafe fn untie() {
-	APP.sending_queue_add(RXTX_UNTIE_FLAG.to_string());
+	APP.client_do(|c| c.untie());
 	set_state(AppState::Chat(Chat::default()));
 }
 
+/// Whether current state is a `Job` that is still in progress
+unsafe fn job_in_progress() -> bool {
+	matches!(
+		APP.state,
+		AppState::Job(Job {
+			state: JobState::InProgress(_),
+			..
+		})
+	)
+}
+
+/// Run job in its own task, failing it if the server takes longer than configured to answer.
+/// The task lasts until the job is done (including waiting for the subject, which isn't timed) and can be aborted
+unsafe fn run_job(start: impl std::future::Future<Output = ()> + Send + 'static) {
+	if let Some(task) = APP.job_task.take() {
+		task.abort();
+	}
+	let timeout = APP.config.job_timeout();
+	APP.job_waiting = false;
+	let job = async move {
+		start.await;
+		while job_in_progress() && !APP.job_waiting {
+			tokio::time::sleep(time::Duration::from_millis(50)).await;
+		}
+	};
+	let task = tokio::spawn(async move {
+		let timed_out = match timeout {
+			Some(timeout) => tokio::time::timeout(timeout, job).await.is_err(),
+			None => {
+				job.await;
+				false
+			}
+		};
+		if let (true, Some(timeout)) = (timed_out, timeout) {
+			if let AppState::Job(Job {
+				state: JobState::InProgress(switch),
+				title,
+				..
+			}) = &APP.state
+			{
+				if *title == TIE_JOB {
+					APP.client_do(|c| c.untie());
+				}
+				let switch = switch.clone().unwrap_or(JobSwitchAppState::Auth);
+				APP.job_log_add(
+					LogLevel::Fault,
+					&format!("{} {}s", JOB_TIMEOUT, timeout.as_secs()),
+				);
+				APP.job_state_set(JobState::Err(switch), false);
+			}
+			return;
+		}
+		while job_in_progress() {
+			tokio::time::sleep(time::Duration::from_millis(50)).await;
+		}
+	});
+	APP.job_task = Some(task.abort_handle());
+}
+
+/// Save current job's log to a file for bug reports, logging the result
+unsafe fn job_log_save() {
+	let title = match &APP.state {
+		AppState::Job(job) => job.title.clone(),
+		_ => return,
+	};
+	let dir = APP
+		.config
+		.export_dir
+		.clone()
+		.unwrap_or_else(|| std::path::PathBuf::from("."));
+	match log::save(&dir, &title, &APP.job_log) {
+		Ok(path) => APP.job_log_add(LogLevel::Info, &format!("{} {}", LOG_SAVED, path.display())),
+		Err(err) => APP.job_log_add(LogLevel::Fault, &format!("{}: {}", LOG_SAVE_FAULT, err)),
+	}
+}
+
+/// Cancel current job and leave it for the given state
+unsafe fn job_abort(switch: JobSwitchAppState) {
+	if let Some(task) = APP.job_task.take() {
+		task.abort();
+	}
+	if let AppState::Job(job) = &APP.state {
+		if job.title == TIE_JOB {
+			APP.client_do(|c| c.untie());
+		}
+	}
+	set_state_using_switch(switch);
+}
+
 /// Change App's state to `Job` and begin tying
 async unsafe fn start_tie_job() {
 	let subject = APP.inputs[0].clone();
+	APP.subject_status = None;
 	let mut job = Job::default(TIE_JOB.to_string());
 	job.data = vec![subject.clone()];
 	job.state = JobState::InProgress(Some(JobSwitchAppState::Chat(Chat::default())));
@@ -747,394 +1509,186 @@ async unsafe fn start_tie_job() {
 	};
 	set_state(AppState::Job(job));
 	if untie_required {
-		APP.job_log_add(&format!("{}", TIE_JOB_UNTYING));
-		APP.sending_queue_add(RXTX_UNTIE_FLAG.to_string());
-		thread::sleep(time::Duration::from_millis(500));
+		APP.job_log_add(LogLevel::Info, &TIE_JOB_UNTYING);
+		APP.client_do(|c| c.untie());
+		tokio::time::sleep(time::Duration::from_millis(500)).await;
 	}
-	APP.job_log_add(&format!("{} {}...", TIE_JOB_WITH, subject));
-	thread::sleep(time::Duration::from_millis(500));
-	APP.sending_queue_add(format!("{}{}", TX_TIE_INIT_FLAG, subject));
+	APP.job_log_add(LogLevel::Info, &format!("{} {}...", TIE_JOB_WITH, subject));
+	tokio::time::sleep(time::Duration::from_millis(500)).await;
+	APP.client_do(|c| c.tie(&subject));
 }
 
 /// Change App's state to `Job` and begin authorization
 async unsafe fn start_auth_job() {
-	APP.user_key = Some(UserKey::default(APP.inputs[0].clone()));
-	set_state(AppState::Job(Job::default(AUTH_JOB.to_string())));
-	APP.job_log_add(JOB_STARTING);
-	APP.job_log_add(AUTH_JOB_PRECONNECT);
-	let res = reqwest::get(format!(
-		"http://{}/{}",
-		APP.server.root_url, "preconnect.php"
-	))
-	.await;
+	APP.user_key = Some(UserKey::new(APP.inputs[0].clone()));
+	let mut job = Job::default(AUTH_JOB.to_string());
+	job.state = JobState::InProgress(Some(JobSwitchAppState::Auth));
+	set_state(AppState::Job(job));
+	APP.job_log_add(LogLevel::Info, &JOB_STARTING);
+	APP.job_log_add(LogLevel::Info, &AUTH_JOB_PRECONNECT);
+	let res = preconnect().await;
 	APP.job_progress_set(25);
 	// I'm EXTREMELY sorry but I do slow things down purposefully just to enjoy the cool interfaces
-	thread::sleep(time::Duration::from_millis(200));
-	if res.is_ok() {
-		APP.job_log_add(JOB_SUCCESS);
-		let txt = res.unwrap().text().await;
-		if txt.is_ok() {
-			if txt.as_ref().unwrap() == "Ok" {
-				APP.job_log_add(AUTH_JOB_CONNECT);
-				APP.job_progress_set(50);
-				let connection = ws_connect().await;
-				APP.job_progress_set(70);
-				match connection {
-					Err(_) => {
-						APP.job_log_add(AUTH_JOB_CONNECT_FAULT);
-						APP.job_state_set(JobState::Err(JobSwitchAppState::Auth), false);
-						return;
-					}
-					Ok(ok) => {
-						APP.job_log_add(JOB_SUCCESS);
-						let (write, read) = ok.split();
-						//tokio::spawn(write_ws(write));
-						thread::spawn(|| {
-							// Some expensive computation.
-							write_ws(write)
-						});
-						tokio::spawn(read_ws(read));
-						APP.job_progress_set(90);
-						APP.job_log_add(AUTH_JOB_CONNECT_AUTH);
-						// FIXME:
-						// Now this one was added as a stability precaution...
-						thread::sleep(time::Duration::from_millis(100));
-						APP.sending_queue_add(format!(
-							"{}{}/{}",
-							TX_AUTH_FLAG,
-							APP.server.key,
-							APP.user_key.clone().unwrap().full
-						));
-					}
-				};
-			} else {
-				APP.job_log_add(AUTH_JOB_PRECONNECT_FAULT_DISAPPROVED);
-				APP.job_state_set(JobState::Err(JobSwitchAppState::Auth), false);
-				return;
-			}
-		} else {
-			APP.job_log_add(AUTH_JOB_PRECONNECT_FAULT_PARSE);
-			APP.job_state_set(JobState::Err(JobSwitchAppState::Auth), false);
-			return;
-		}
-	} else {
-		APP.job_log_add(AUTH_JOB_PRECONNECT_FAULT_GET);
+	tokio::time::sleep(time::Duration::from_millis(200)).await;
+	if let Err(fault) = res {
+		APP.job_log_add(LogLevel::Fault, &fault);
 		APP.job_state_set(JobState::Err(JobSwitchAppState::Auth), false);
 		return;
 	}
-}
-
-/// Renders app's `Job` state UI
-unsafe fn job_ui<B: Backend>(f: &mut Frame<B>) {
-	// TODO: Typing indicator
-	// if APP.typing_state_iteration >= 3 {
-	// 	APP.typing_state_iteration = 0
-	// } else {
-	// 	APP.typing_state_iteration += 1
-	// }
-	// ( .title(strings::MESSAGES_BLOCK_TYPING[APP.typing_state_iteration as usize]) )
-	match &APP.state {
-		AppState::Job(job) => {
-			// FIXME:
-			// Shotgun approach (aimed to fix #2)
-			let progress = job.progress;
-			#[allow(unused_comparisons)]
-			if progress < 0 || progress > 100 {
-				return;
-			};
-			let chunks = Layout::default()
-				.direction(Direction::Vertical)
-				.vertical_margin(2)
-				.horizontal_margin(12)
-				.constraints([Constraint::Min(1)].as_ref())
-				.split(f.size());
-			let main_window = Block::default()
-				.borders(Borders::NONE)
-				.title(job.title.clone())
-				.title_alignment(Alignment::Center)
-				.style(Style::default().bg(match job.state {
-					JobState::InProgress(_) => Color::DarkGray,
-					JobState::Ok(_) => Color::Green,
-					JobState::Err(_) => Color::Red,
-				}));
-			f.render_widget(main_window, chunks[0]);
-			{
-				let chunks = Layout::default()
-					.direction(Direction::Vertical)
-					.vertical_margin(2)
-					.horizontal_margin(4)
-					.constraints(
-						[
-							Constraint::Length(3),
-							Constraint::Min(1),
-							Constraint::Length(1),
-							Constraint::Length(1),
-						]
-						.as_ref(),
-					)
-					.split(chunks[0]);
-				let progress_bar = Gauge::default()
-					.gauge_style(Style::default().fg(Color::White))
-					.percent(progress)
-					.label(Span::styled(
-						format!("{}%", job.progress),
-						Style::default().fg(Color::Black),
-					))
-					.block(
-						Block::default()
-							.borders(Borders::ALL)
-							.style(Style::default().fg(Color::White)),
-					);
-				f.render_widget(progress_bar, chunks[0]);
-				// TODO:
-				// It'd be cool to place `ListItem`s into `Job` instead of `String`s, so I could have the log formatted differently
-				let log_messages: Vec<ListItem> = JOB_LOG
-					.iter()
-					.enumerate()
-					.map(|(_, m)| {
-						let content = vec![Spans::from(Span::raw(m))];
-						ListItem::new(content)
-					})
-					.collect();
-				let log = List::new(log_messages).block(
-					Block::default()
-						.borders(Borders::ALL)
-						.style(Style::default().fg(Color::White))
-						.title(LOG_BLOCK)
-						.title_alignment(Alignment::Center),
-				);
-				f.render_widget(log, chunks[1]);
-				match &job.state {
-					JobState::InProgress(switch) => {
-						if switch.is_some() {
-							let prompt = Paragraph::new(ABORT_PROMPT).alignment(Alignment::Center);
-							f.render_widget(prompt, chunks[3])
-						}
-					}
-					_ => {
-						let prompt = Paragraph::new(CONTINUE_PROMPT).alignment(Alignment::Center);
-						f.render_widget(prompt, chunks[3])
-					}
-				}
-			}
+	APP.job_log_add(LogLevel::Success, &JOB_SUCCESS);
+	APP.job_log_add(LogLevel::Info, &AUTH_JOB_CONNECT);
+	APP.job_progress_set(50);
+	let connection = socket_connect().await;
+	APP.job_progress_set(70);
+	match connection {
+		Err(_) => {
+			APP.job_log_add(LogLevel::Fault, &AUTH_JOB_CONNECT_FAULT);
+			APP.job_state_set(JobState::Err(JobSwitchAppState::Auth), false);
 		}
-		_ => {
-			// TODO:
-			// More descriptive errors (maybe)
-			panic!("{}", FATAL_RUNTIME_ERROR);
+		Ok((mut client, events)) => {
+			APP.job_log_add(LogLevel::Success, &JOB_SUCCESS);
+			tokio::spawn(read_events(events));
+			APP.job_progress_set(90);
+			APP.job_log_add(LogLevel::Info, &AUTH_JOB_CONNECT_AUTH);
+			client.auth(&APP.server.key, APP.user_key.as_ref().unwrap()).ok();
+			APP.client = Some(client);
 		}
-	}
+	};
 }
 
-/// Renders app's `Auth` state UI
-unsafe fn auth_ui<B: Backend>(f: &mut Frame<B>) {
-	let chunks = Layout::default()
-		.direction(Direction::Vertical)
-		.margin(1)
-		.constraints(
-			[
-				Constraint::Length(6),
-				Constraint::Length(3),
-				Constraint::Min(1),
-				Constraint::Length(4),
-			]
-			.as_ref(),
-		)
-		.split(f.size());
-	let header = Paragraph::new(format!(
-		"{}v{} ({})",
-		LOGO,
-		env!("CARGO_PKG_VERSION"),
-		APP.server.name
-	))
-	.style(if APP.input_focus == 0 {
-		Style::default().fg(Color::Cyan)
-	} else {
-		Style::default()
-	});
-	f.render_widget(header, chunks[0]);
-	let input = Paragraph::new(APP.inputs[0].as_ref())
-		.style(if APP.input_focus == 1 {
-			Style::default().fg(Color::Cyan)
-		} else {
-			Style::default()
-		})
-		.block(
-			Block::default()
-				.borders(Borders::ALL)
-				.title(if APP.input_focus == 1 {
-					AUTH_KEY_BLOCK_ACTIVE
-				} else {
-					AUTH_KEY_BLOCK_INACTIVE
-				})
-				.border_type(if APP.input_focus == 1 {
-					BorderType::Thick
-				} else {
-					BorderType::Double
-				}),
-		);
-	f.render_widget(input.clone(), chunks[1]);
-	let instructions = Paragraph::new(USAGE_INSTRUCTIONS);
-	f.render_widget(instructions, chunks[3]);
-	if APP.input_focus == 1 {
-		f.set_cursor(
-			chunks[1].x + APP.inputs[0].width() as u16 + 1,
-			chunks[1].y + 1,
-		)
+/// Get server endpoint to connect to
+unsafe fn endpoint() -> Endpoint {
+	Endpoint::new(&APP.server.root_url, &APP.server.port)
+}
+
+/// Open socket connection to the server, tracing it if asked to
+async unsafe fn socket_connect() -> Result<(Client, Events), husky::Error> {
+	match &APP.trace {
+		Some(trace) => Client::connect_traced(&endpoint(), trace.clone()).await,
+		None => Client::connect(&endpoint()).await,
 	}
 }
 
-/// Renders app's `Chat` state UI
-unsafe fn chat_ui<B: Backend>(f: &mut Frame<B>) {
-	match &APP.state {
-		AppState::Chat(chat) => {
-			let tied = if let ChatState::Tied(_) = chat.state {
-				true
-			} else {
-				false
-			};
-			let chunks = Layout::default()
-				.direction(Direction::Vertical)
-				.constraints(
-					[
-						Constraint::Length(2),
-						Constraint::Length(3),
-						Constraint::Length(3),
-						Constraint::Min(1),
-						Constraint::Length(3),
-					]
-					.as_ref(),
-				)
-				.split(f.size());
-			let cs = match &chat.state {
-				ChatState::Untied => CHAT_STATE_UNTIED.to_string(),
-				ChatState::Tied(a) => format!("{} {}", CHAT_STATE_TIED_WITH, a),
-			};
-			let hint = if APP.input_focus == 0 {
-				if tied {
-					CHAT_STATE_UNTIE_PROMPT
-				} else {
-					CHAT_STATE_LOGOUT_PROMPT
-				}
-			} else {
-				""
-			};
-			let header = Paragraph::new(format!(
-				"Husky v{} / {} / {}{}",
-				env!("CARGO_PKG_VERSION"),
-				APP.user_key.as_ref().unwrap().username,
-				cs,
-				hint
-			))
-			.style(if APP.input_focus == 0 {
-				Style::default().fg(Color::Cyan)
-			} else {
-				Style::default()
-			});
-			f.render_widget(header, chunks[0]);
-			let in_focus = APP.input_focus == 1;
-			let subject_input = Paragraph::new(if !in_focus && tied {
-				USERNAME_BLOCK_FILL_TIED
-			} else {
-				APP.inputs[0].as_ref()
-			})
-			.style(if in_focus {
-				Style::default().fg(Color::Cyan)
-			} else {
-				Style::default()
-			})
-			.block(
-				Block::default()
-					.borders(Borders::ALL)
-					.title(if in_focus {
-						USERNAME_BLOCK_ACTIVE
-					} else {
-						USERNAME_BLOCK_INACTIVE
-					})
-					.border_type(if in_focus {
-						BorderType::Thick
-					} else {
-						BorderType::Double
-					}),
-			);
-			f.render_widget(subject_input, chunks[1]);
-			let encryption_key_input = Paragraph::new(APP.inputs[1].as_ref())
-				.style(match APP.input_focus {
-					2 => Style::default().fg(Color::Cyan),
-					_ => {
-						if tied {
-							Style::default()
-						} else {
-							Style::default().fg(Color::DarkGray)
-						}
-					}
-				})
-				.block(
-					Block::default()
-						.borders(Borders::ALL)
-						.title(ENCRYPTION_KEY_BLOCK)
-						.border_type(match APP.input_focus {
-							2 => BorderType::Thick,
-							_ => BorderType::Double,
-						}),
-				);
-			f.render_widget(encryption_key_input, chunks[2]);
-			let messages: Vec<ListItem> = CHAT_MESSAGES
-				.iter()
-				.enumerate()
-				.map(|(_, m)| {
-					let content = vec![Spans::from(Span::raw(m))];
-					ListItem::new(content)
-				})
-				.collect();
-			let messages = List::new(messages)
-				.block(
-					Block::default()
-						.style(Style::default().fg(Color::Gray))
-						.borders(Borders::ALL),
-				)
-				.start_corner(Corner::BottomLeft);
-			f.render_widget(messages, chunks[3]);
-			let new_message_input = Paragraph::new(APP.inputs[2].as_ref())
-				.style(match APP.input_focus {
-					3 => Style::default().fg(Color::Cyan),
-					_ => {
-						if tied {
-							Style::default()
-						} else {
-							Style::default().fg(Color::DarkGray)
-						}
-					}
-				})
-				.block(
-					Block::default()
-						.borders(Borders::ALL)
-						.title(match APP.input_focus {
-							3 => NEW_MESSAGE_BLOCK_ACTIVE,
-							_ => NEW_MESSAGE_BLOCK_INACTIVE,
-						})
-						.border_type(match APP.input_focus {
-							3 => BorderType::Thick,
-							_ => BorderType::Double,
-						}),
-				);
-			f.render_widget(new_message_input, chunks[4]);
-			if APP.input_focus != 0 {
-				f.set_cursor(
-					chunks[(APP.input_focus) as usize].x
-						+ APP.inputs[(APP.input_focus - 1) as usize].width() as u16
-						+ 1,
-					chunks[(if APP.input_focus == 3 {
-						4
-					} else {
-						APP.input_focus
-					}) as usize]
-						.y + 1,
-				)
+/// Make sure server daemon is up and approves connections, returning a fault description otherwise
+async unsafe fn preconnect() -> Result<(), Localized> {
+	Client::preconnect(&endpoint()).await.map_err(|err| match err {
+		husky::Error::PreconnectDisapproved => AUTH_JOB_PRECONNECT_FAULT_DISAPPROVED,
+		husky::Error::PreconnectUnparsable => AUTH_JOB_PRECONNECT_FAULT_PARSE,
+		_ => AUTH_JOB_PRECONNECT_FAULT_GET,
+	})
+}
+
+#[cfg(test)]
+#[path = "../tests/support/mod.rs"]
+mod support;
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use support::{MockServer, ACCESS_KEY};
+
+	/// `APP` is global, so tests using it take turns
+	static APP_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+	/// Reset `APP` to talk to the given server
+	unsafe fn app_with(server: &MockServer) {
+		APP = App::null();
+		let endpoint = server.endpoint();
+		APP.server.root_url = endpoint.root_url;
+		APP.server.port = endpoint.port;
+		APP.server.key = ACCESS_KEY.to_string();
+	}
+
+	/// Wait for `condition` to hold, as the app gets events in its own task
+	async fn until(condition: impl Fn() -> bool) {
+		tokio::time::timeout(Duration::from_secs(5), async {
+			while !condition() {
+				tokio::time::sleep(Duration::from_millis(10)).await;
 			}
+		})
+		.await
+		.expect("condition not met in time")
+	}
+
+	unsafe fn job_state() -> Option<JobState> {
+		match &APP.state {
+			AppState::Job(job) => Some(job.state.clone()),
+			_ => None,
 		}
-		_ => {
-			panic!("{}", FATAL_RUNTIME_ERROR);
+	}
+
+	unsafe fn message_states() -> Vec<(String, String, MessageState)> {
+		APP.chat_messages
+			.iter()
+			.map(|m| (m.sender.clone(), m.text.clone(), m.state))
+			.collect()
+	}
+
+	async fn login(server: &MockServer, key: &str) -> (Client, Events) {
+		let (mut client, mut events) = Client::connect(&server.endpoint()).await.unwrap();
+		client.auth(ACCESS_KEY, &UserKey::new(key.to_string())).unwrap();
+		assert_eq!(events.next().await.unwrap().unwrap(), husky::Event::AuthOk);
+		(client, events)
+	}
+
+	#[tokio::test]
+	async fn auth_fault() {
+		let _lock = APP_LOCK.lock().await;
+		let server = MockServer::start(&["alice:wonderland"]).await;
+		unsafe {
+			app_with(&server);
+			APP.inputs[0] = "alice:rabbit".to_string();
+			start_auth_job().await;
+			until(|| !job_in_progress()).await;
+			assert!(job_state() == Some(JobState::Err(JobSwitchAppState::Auth)));
+			assert!(APP.job_log.iter().any(|e| e.message == AUTH_JOB_CONNECT_AUTH_FAULT));
+		}
+	}
+
+	#[tokio::test]
+	async fn auth_tie_chat_untie() {
+		let _lock = APP_LOCK.lock().await;
+		let server = MockServer::start(&["alice:wonderland", "bob:builder"]).await;
+		let (bob, mut bob_events) = login(&server, "bob:builder").await;
+		unsafe {
+			app_with(&server);
+			APP.inputs[0] = "alice:wonderland".to_string();
+			start_auth_job().await;
+			until(|| !job_in_progress()).await;
+			let switch = JobSwitchAppState::Chat(Chat::default());
+			assert!(job_state() == Some(JobState::Ok(switch.clone())));
+			set_state_using_switch(switch);
+
+			// Alice ties first and waits for Bob
+			APP.inputs[0] = "bob".to_string();
+			start_tie_job().await;
+			until(|| APP.job_waiting).await;
+			assert!(job_in_progress());
+			bob.tie("alice").unwrap();
+			assert_eq!(bob_events.next().await.unwrap().unwrap(), husky::Event::TieOk);
+			until(|| !job_in_progress()).await;
+			let switch = JobSwitchAppState::Chat(Chat::with_subject("bob".to_string()));
+			assert!(job_state() == Some(JobState::Ok(switch.clone())));
+			set_state_using_switch(switch);
+
+			bob.send("Hi").unwrap();
+			until(|| !APP.chat_messages.is_empty()).await;
+			assert_eq!(
+				message_states(),
+				[("bob".to_string(), "Hi".to_string(), MessageState::Received)]
+			);
+
+			// Own message is pending until the server echoes it back
+			APP.inputs[2] = "Hello".to_string();
+			submit_message().await;
+			assert_eq!(APP.chat_messages[0].state, MessageState::Pending);
+			until(|| APP.chat_messages[0].state == MessageState::Delivered).await;
+			assert_eq!(APP.chat_messages.len(), 2);
+
+			bob.untie().unwrap();
+			until(|| job_state().is_some()).await;
+			assert!(job_state() == Some(JobState::Err(JobSwitchAppState::Chat(Chat::default()))));
+			assert!(APP.job_log.iter().any(|e| e.message == TIE_BROKEN));
 		}
 	}
 }
@@ -0,0 +1,145 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use crossterm::Command as TerminalCommand;
+use serde::Deserialize;
+use std::{env, fmt, io, process::Stdio};
+use tokio::process::Command;
+
+/// How to get the user's attention when a message arrives while the terminal is unfocused
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum NotifyMethod {
+	/// Only count unread messages in the terminal title
+	Off,
+	Bell,
+	/// Desktop notification supported by iTerm2, kitty, WezTerm and others
+	Osc9,
+	/// Desktop notification supported by rxvt-unicode, foot, Konsole and others
+	Osc777,
+}
+
+/// Message that arrived while the terminal was unfocused
+#[derive(PartialEq, Clone, Debug)]
+pub struct Notice {
+	pub sender: String,
+	pub text: String,
+}
+
+/// Save terminal title before it's changed (`Push`), or bring it back on exit (`Pop`)
+pub enum TitleStack {
+	Push,
+	Pop,
+}
+
+impl TerminalCommand for TitleStack {
+	fn write_ansi(&self, f: &mut impl fmt::Write) -> fmt::Result {
+		// xterm title stack, which terminals without it simply ignore
+		f.write_str(match self {
+			TitleStack::Push => "\x1b[22;0t",
+			TitleStack::Pop => "\x1b[23;0t",
+		})
+	}
+	#[cfg(windows)]
+	fn execute_winapi(&self) -> crossterm::Result<()> {
+		Ok(())
+	}
+}
+
+/// Get terminal title with the number of unread messages
+pub fn title(unread: usize) -> String {
+	if unread == 0 {
+		"Husky".to_string()
+	} else {
+		format!("({}) Husky", unread)
+	}
+}
+
+/// Get escape sequence notifying about the message, wrapped to get through tmux if `tmux` is set
+fn sequence(method: NotifyMethod, notice: &Notice, tmux: bool) -> String {
+	// Anything that could end the sequence early is escaped
+	let sender = husky::sanitize(&notice.sender, true).0.replace(';', ",");
+	let text = husky::sanitize(&notice.text, true).0;
+	let sequence = match method {
+		NotifyMethod::Off => return String::new(),
+		// tmux flags the window on its own, no need to pass bell through
+		NotifyMethod::Bell => return "\x07".to_string(),
+		NotifyMethod::Osc9 => format!("\x1b]9;{}: {}\x07", sender, text),
+		NotifyMethod::Osc777 => format!("\x1b]777;notify;{};{}\x07", sender, text),
+	};
+	if tmux {
+		format!("\x1bPtmux;{}\x1b\\", sequence.replace('\x1b', "\x1b\x1b"))
+	} else {
+		sequence
+	}
+}
+
+/// Write notification about the message to the terminal
+pub fn alert(out: &mut impl io::Write, method: NotifyMethod, notice: &Notice) -> io::Result<()> {
+	let tmux = env::var_os("TMUX").is_some();
+	out.write_all(sequence(method, notice, tmux).as_bytes())?;
+	out.flush()
+}
+
+/// Run user's hook command through the shell, passing the message in `$HUSKY_SENDER` and `$HUSKY_MESSAGE`
+pub fn hook(command: &str, notice: &Notice) -> io::Result<()> {
+	let mut shell = if cfg!(windows) {
+		let mut shell = Command::new("cmd");
+		shell.arg("/C");
+		shell
+	} else {
+		let mut shell = Command::new("sh");
+		shell.arg("-c");
+		shell
+	};
+	// Whatever the command prints would end up all over the interface
+	shell
+		.arg(command)
+		.env("HUSKY_SENDER", &notice.sender)
+		.env("HUSKY_MESSAGE", &notice.text)
+		.stdin(Stdio::null())
+		.stdout(Stdio::null())
+		.stderr(Stdio::null())
+		.spawn()?;
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn notice(text: &str) -> Notice {
+		Notice {
+			sender: "bob".to_string(),
+			text: text.to_string(),
+		}
+	}
+
+	#[test]
+	fn unread_are_counted_in_title() {
+		assert_eq!(title(0), "Husky");
+		assert_eq!(title(3), "(3) Husky");
+	}
+
+	#[test]
+	fn notification_cannot_be_cut_short() {
+		assert_eq!(
+			sequence(NotifyMethod::Osc777, &notice("hi\x07\x1b]0;pwned\x07"), false),
+			"\x1b]777;notify;bob;hi\\u{7}\\u{1b}]0;pwned\\u{7}\x07"
+		);
+		assert_eq!(sequence(NotifyMethod::Osc9, &notice("hi"), false), "\x1b]9;bob: hi\x07");
+	}
+
+	#[test]
+	fn notification_passes_through_tmux() {
+		assert_eq!(
+			sequence(NotifyMethod::Osc9, &notice("hi"), true),
+			"\x1bPtmux;\x1b\x1b]9;bob: hi\x07\x1b\\"
+		);
+		assert_eq!(sequence(NotifyMethod::Bell, &notice("hi"), true), "\x07");
+		assert_eq!(sequence(NotifyMethod::Off, &notice("hi"), true), "");
+	}
+}
@@ -0,0 +1,282 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use crate::flags::*;
+use chrono::{DateTime, TimeZone, Utc};
+use std::time::Duration;
+
+/// Something that came from the server
+#[derive(PartialEq, Clone, Debug)]
+pub enum Event {
+	AuthOk,
+	AuthFault,
+	AuthFaultOverauth,
+	TieOk,
+	/// Tie requested, waiting for the subject to tie back
+	TieWait,
+	TieFaultNoUser,
+	TieFaultSelfTie,
+	TieFaultOverTie,
+	/// Tie was broken by the subject
+	Untie,
+	Message {
+		sender: String,
+		text: String,
+		/// When the sender sent it (not known for messages from older clients)
+		time: Option<DateTime<Utc>>,
+		/// How long after being sent the message is to be removed on both ends (`None` to keep it)
+		ttl: Option<Duration>,
+	},
+	/// Tie subject's status changed
+	Status(Status),
+	/// Tie subject wants to send a file
+	FileOffer(FileOffer),
+	/// Tie subject accepted the offered file
+	FileAccept,
+	/// Tie subject declined the offered file or cancelled the transfer
+	FileDecline,
+	/// Next piece of the file being received
+	FileChunk(Vec<u8>),
+	/// Tie subject got the last chunk sent and is ready for the next one
+	FileAck,
+	/// Tie subject got the whole file and checked its integrity
+	FileDone {
+		verified: bool,
+	},
+	Ok,
+	Fault,
+	/// Frame with a flag this version doesn't know about
+	Unknown(String),
+}
+
+impl Event {
+	/// Decode text frame received from socket
+	pub fn parse(frame: &str) -> Event {
+		let mut chars = frame.chars();
+		let flag = match chars.next() {
+			Some(flag) => flag,
+			None => return Event::Unknown(String::new()),
+		};
+		let body = chars.as_str();
+		match flag {
+			RX_AUTH_OK_FLAG => Event::AuthOk,
+			RX_AUTH_FAULT_FLAG => Event::AuthFault,
+			RX_AUTH_FAULT_OVERAUTH_FLAG => Event::AuthFaultOverauth,
+			RX_TIE_OK_FLAG => Event::TieOk,
+			RX_TIE_OK_WAIT_FLAG => Event::TieWait,
+			RX_TIE_FAULT_NOUSER_FLAG => Event::TieFaultNoUser,
+			RX_TIE_FAULT_SELFTIE_FLAG => Event::TieFaultSelfTie,
+			RX_TIE_FAULT_OVERTIE_FLAG => Event::TieFaultOverTie,
+			RXTX_UNTIE_FLAG => Event::Untie,
+			RXTX_MESSAGE_FLAG => {
+				let (time, body) = message_time_decode(body);
+				let (ttl, body) = message_ttl_decode(body);
+				let (sender, text) = body.split_once(": ").unwrap_or(("", body));
+				Event::Message {
+					sender: sender.to_string(),
+					text: text.to_string(),
+					time,
+					ttl,
+				}
+			}
+			RXTX_STATUS_FLAG => match Status::decode(body) {
+				Some(status) => Event::Status(status),
+				None => Event::Unknown(frame.to_string()),
+			},
+			RXTX_FILE_OFFER_FLAG => match FileOffer::decode(body) {
+				Some(offer) => Event::FileOffer(offer),
+				None => Event::Unknown(frame.to_string()),
+			},
+			RXTX_FILE_ACCEPT_FLAG => Event::FileAccept,
+			RXTX_FILE_DECLINE_FLAG => Event::FileDecline,
+			RXTX_FILE_CHUNK_FLAG => match base64::decode(body) {
+				Ok(chunk) => Event::FileChunk(chunk),
+				Err(_) => Event::Unknown(frame.to_string()),
+			},
+			RXTX_FILE_ACK_FLAG => Event::FileAck,
+			RXTX_FILE_DONE_FLAG => Event::FileDone {
+				verified: body.starts_with(FILE_VERIFIED_ATTR),
+			},
+			RXTX_OK_FLAG => Event::Ok,
+			RXTX_FAULT_FLAG => Event::Fault,
+			_ => Event::Unknown(frame.to_string()),
+		}
+	}
+}
+
+/// Encode message to be sent to the tie subject, stamped with the time it's sent at
+pub(crate) fn message_encode(sender: &str, text: &str, time: DateTime<Utc>, ttl: Option<Duration>) -> String {
+	format!(
+		"{}{}{} {}{}: {}",
+		RXTX_MESSAGE_FLAG,
+		MESSAGE_TIME_ATTR,
+		time.timestamp_millis(),
+		match ttl {
+			Some(ttl) => format!("{}{} ", MESSAGE_TTL_ATTR, ttl.as_secs()),
+			None => String::new(),
+		},
+		sender,
+		text
+	)
+}
+
+/// Split sender's timestamp off message body (flag excluded), if it's there
+fn message_time_decode(body: &str) -> (Option<DateTime<Utc>>, &str) {
+	let stamped = body
+		.strip_prefix(MESSAGE_TIME_ATTR)
+		.and_then(|rest| rest.split_once(' '))
+		.and_then(|(millis, rest)| Some((Utc.timestamp_millis_opt(millis.parse().ok()?).single()?, rest)));
+	match stamped {
+		Some((time, rest)) => (Some(time), rest),
+		None => (None, body),
+	}
+}
+
+/// Split message lifetime off message body (timestamp excluded), if it's there
+fn message_ttl_decode(body: &str) -> (Option<Duration>, &str) {
+	let ttl = body
+		.strip_prefix(MESSAGE_TTL_ATTR)
+		.and_then(|rest| rest.split_once(' '))
+		.and_then(|(secs, rest)| Some((Duration::from_secs(secs.parse().ok()?), rest)));
+	match ttl {
+		Some((ttl, rest)) => (Some(ttl), rest),
+		None => (None, body),
+	}
+}
+
+/// The user's auth key data is stored here
+#[derive(PartialEq, Clone, Debug)]
+pub struct UserKey {
+	pub full: String,
+	pub username: String,
+}
+
+impl UserKey {
+	/// Make user key from `username:password` string
+	pub fn new(with_full: String) -> UserKey {
+		UserKey {
+			username: with_full.split(':').next().unwrap_or_default().to_string(),
+			full: with_full,
+		}
+	}
+}
+
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Presence {
+	Online,
+	Away,
+}
+
+/// The user's presence along with a custom status message is stored here
+#[derive(PartialEq, Clone, Debug)]
+pub struct Status {
+	pub presence: Presence,
+	pub message: String,
+}
+
+impl Status {
+	/// Encode status to be sent to the tie subject
+	pub fn encode(&self) -> String {
+		format!(
+			"{}{}{}",
+			RXTX_STATUS_FLAG,
+			match self.presence {
+				Presence::Online => STATUS_ONLINE_ATTR,
+				Presence::Away => STATUS_AWAY_ATTR,
+			},
+			self.message
+		)
+	}
+	/// Decode status received from the tie subject (flag excluded)
+	pub fn decode(body: &str) -> Option<Status> {
+		let mut chars = body.chars();
+		let presence = match chars.next()? {
+			STATUS_ONLINE_ATTR => Presence::Online,
+			STATUS_AWAY_ATTR => Presence::Away,
+			_ => return None,
+		};
+		Some(Status {
+			presence,
+			message: chars.collect(),
+		})
+	}
+}
+
+/// File the tie subject is asked to accept
+#[derive(PartialEq, Clone, Debug)]
+pub struct FileOffer {
+	/// File name, without any directories
+	pub name: String,
+	/// Size in bytes
+	pub size: u64,
+	/// Hex-encoded SHA-256 of the contents
+	pub sha256: String,
+}
+
+impl FileOffer {
+	/// Encode offer to be sent to the tie subject
+	pub fn encode(&self) -> String {
+		format!("{}{}:{}:{}", RXTX_FILE_OFFER_FLAG, self.size, self.sha256, self.name)
+	}
+	/// Decode offer received from the tie subject (flag excluded)
+	pub fn decode(body: &str) -> Option<FileOffer> {
+		let mut parts = body.splitn(3, ':');
+		let size = parts.next()?.parse().ok()?;
+		let sha256 = parts.next()?.to_string();
+		let name = parts.next()?.to_string();
+		Some(FileOffer { name, size, sha256 })
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn message_carries_sender_time() {
+		let time = Utc.timestamp_millis_opt(1651408200123).unwrap();
+		let frame = message_encode("alice", "Hi: Bob", time, None);
+		assert_eq!(frame, "B@1651408200123 alice: Hi: Bob");
+		assert_eq!(
+			Event::parse(&frame),
+			Event::Message {
+				sender: "alice".to_string(),
+				text: "Hi: Bob".to_string(),
+				time: Some(time),
+				ttl: None,
+			}
+		);
+	}
+
+	#[test]
+	fn ephemeral_message_carries_ttl() {
+		let time = Utc.timestamp_millis_opt(1651408200123).unwrap();
+		let frame = message_encode("alice", "~1 is approximately one", time, Some(Duration::from_secs(30)));
+		assert_eq!(frame, "B@1651408200123 ~30 alice: ~1 is approximately one");
+		assert_eq!(
+			Event::parse(&frame),
+			Event::Message {
+				sender: "alice".to_string(),
+				text: "~1 is approximately one".to_string(),
+				time: Some(time),
+				ttl: Some(Duration::from_secs(30)),
+			}
+		);
+	}
+
+	#[test]
+	fn message_from_older_client_has_no_time() {
+		assert_eq!(
+			Event::parse("Balice: @noon works?"),
+			Event::Message {
+				sender: "alice".to_string(),
+				text: "@noon works?".to_string(),
+				time: None,
+				ttl: None,
+			}
+		);
+	}
+}
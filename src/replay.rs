@@ -0,0 +1,84 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use crate::log::LogLevel;
+use crate::strings::ui::*;
+use crate::{
+	handle_event, set_state, set_state_using_switch, AppState, Chat, ChatState, Job, JobState,
+	JobSwitchAppState, APP,
+};
+use husky::flags::*;
+use husky::{Direction, Status, TraceRecord, UserKey};
+use std::time::Duration;
+
+/// Feed recorded frames into the app, keeping their timing (divided by `speed`)
+pub async unsafe fn run(records: Vec<TraceRecord>, speed: f64) {
+	let mut previous: Option<chrono::DateTime<chrono::Utc>> = None;
+	for record in records {
+		if let Some(previous) = previous {
+			let gap = (record.time - previous).to_std().unwrap_or_default();
+			tokio::time::sleep(Duration::from_secs_f64(gap.as_secs_f64() / speed)).await;
+		}
+		previous = Some(record.time);
+		// User must have acknowledged finished job for anything to happen after it
+		if let AppState::Job(Job {
+			state: JobState::Ok(switch) | JobState::Err(switch),
+			..
+		}) = &APP.state
+		{
+			set_state_using_switch(switch.clone());
+		}
+		match record.direction {
+			Direction::Rx => handle_event(husky::Event::parse(&record.frame)),
+			Direction::Tx => apply_sent(&record.frame),
+		}
+	}
+	match APP.state {
+		AppState::Job(_) => APP.job_log_add(LogLevel::Info, &REPLAY_DONE),
+		_ => APP.chat_notice = Some(REPLAY_DONE.to_string()),
+	}
+}
+
+/// Put app into the state it was in when the user sent this frame
+unsafe fn apply_sent(frame: &str) {
+	let mut chars = frame.chars();
+	let flag = chars.next();
+	let body = chars.as_str();
+	match flag {
+		Some(TX_AUTH_FLAG) => {
+			// Recorded key is redacted, only the username is left
+			let user_key = body.split_once('/').map(|(_, k)| k).unwrap_or_default();
+			APP.user_key = Some(UserKey::new(user_key.to_string()));
+			let mut job = Job::default(AUTH_JOB.to_string());
+			job.state = JobState::InProgress(Some(JobSwitchAppState::Auth));
+			set_state(AppState::Job(job));
+			APP.job_log_add(LogLevel::Info, &AUTH_JOB_CONNECT_AUTH);
+		}
+		Some(TX_TIE_INIT_FLAG) => {
+			let mut job = Job::default(TIE_JOB.to_string());
+			job.data = vec![body.to_string()];
+			job.state = JobState::InProgress(Some(JobSwitchAppState::Chat(Chat::default())));
+			APP.subject_status = None;
+			set_state(AppState::Job(job));
+			APP.job_log_add(LogLevel::Info, &format!("{} {}...", TIE_JOB_WITH, body));
+		}
+		Some(RXTX_UNTIE_FLAG) => {
+			if let AppState::Chat(Chat {
+				state: ChatState::Tied(_),
+			}) = APP.state
+			{
+				set_state(AppState::Chat(Chat::default()))
+			}
+		}
+		Some(RXTX_STATUS_FLAG) => {
+			if let Some(status) = Status::decode(body) {
+				APP.status = status
+			}
+		}
+		// Everything else either changes nothing locally or is echoed back by server
+		_ => (),
+	}
+}
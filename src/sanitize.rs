@@ -0,0 +1,81 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+/// Whether character can reorder the text around it
+fn is_bidi_control(c: char) -> bool {
+	matches!(c, '\u{202A}'..='\u{202E}' | '\u{2066}'..='\u{2069}')
+}
+
+/// Whether character breaks the line without being a control character
+fn is_separator(c: char) -> bool {
+	matches!(c, '\u{2028}' | '\u{2029}')
+}
+
+/// Whether character takes no room on screen, making it hard to notice
+fn is_invisible(c: char) -> bool {
+	matches!(
+		c,
+		'\u{00AD}' | '\u{061C}' | '\u{180E}' | '\u{200B}'..='\u{200F}' | '\u{2060}'..='\u{2064}' | '\u{FEFF}'
+	)
+}
+
+/// Make text received from someone else safe to put on a terminal.
+/// Control characters (escape sequences included), line separators and bidi overrides are escaped,
+/// invisible characters are only escaped if `show_invisible` is set.
+/// Returns the safe text and whether it differs from the original
+pub fn sanitize(text: &str, show_invisible: bool) -> (String, bool) {
+	let mut out = String::with_capacity(text.len());
+	let mut altered = false;
+	for c in text.chars() {
+		if c.is_control() {
+			out.extend(c.escape_default());
+		} else if is_bidi_control(c) || is_separator(c) || (show_invisible && is_invisible(c)) {
+			out.extend(c.escape_unicode());
+		} else {
+			out.push(c);
+			continue;
+		}
+		altered = true;
+	}
+	(out, altered)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn plain_text_is_untouched() {
+		assert_eq!(sanitize("Привет, bob! 👋", true), ("Привет, bob! 👋".to_string(), false));
+	}
+
+	#[test]
+	fn control_characters_are_escaped() {
+		assert_eq!(
+			sanitize("\x1b[2Jhi\nthere\x07", false),
+			("\\u{1b}[2Jhi\\nthere\\u{7}".to_string(), true)
+		);
+		assert_eq!(sanitize("\u{9b}31m", false), ("\\u{9b}31m".to_string(), true));
+		assert_eq!(
+			sanitize("one\u{2028}two\u{2029}", false),
+			("one\\u{2028}two\\u{2029}".to_string(), true)
+		);
+	}
+
+	#[test]
+	fn bidi_overrides_are_neutralized() {
+		assert_eq!(
+			sanitize("invoice\u{202E}fdp.exe", false),
+			("invoice\\u{202e}fdp.exe".to_string(), true)
+		);
+	}
+
+	#[test]
+	fn invisible_characters_are_shown_on_demand() {
+		assert_eq!(sanitize("pay\u{200B}pal", false), ("pay\u{200B}pal".to_string(), false));
+		assert_eq!(sanitize("pay\u{200B}pal", true), ("pay\\u{200b}pal".to_string(), true));
+	}
+}
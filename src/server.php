@@ -35,6 +35,13 @@ define("RXTX_UNTIE_FLAG", "C");
 define("RXTX_OK_FLAG", "Y");
 define("RXTX_FAULT_FLAG", "E");
 define("RXTX_MESSAGE_FLAG", "B");
+define("RXTX_STATUS_FLAG", "P");
+define("RXTX_FILE_OFFER_FLAG", "F");
+define("RXTX_FILE_ACCEPT_FLAG", "G");
+define("RXTX_FILE_DECLINE_FLAG", "H");
+define("RXTX_FILE_CHUNK_FLAG", "K");
+define("RXTX_FILE_ACK_FLAG", "L");
+define("RXTX_FILE_DONE_FLAG", "J");
 
 $socket = stream_socket_server(LOCALHOST_PORT, $errno, $errstr);
 
@@ -43,6 +50,7 @@ if (!$socket) {
 }
 
 $queue = array();
+$buffers = array();
 $approved = array();
 $waitlist = array();
 $ties = array();
@@ -67,7 +75,14 @@ while (true) {
 			conn_close($connect);
 			continue;
 		}
-		on_message($connect, $data); // Responding to incoming message
+		// Frames may come in pieces (file chunks don't fit into a single read) or several at once,
+		// so they're collected per connection and handled once complete, never waiting for the rest
+		$id = (int)$connect;
+		$buffers[$id] = (isset($buffers[$id]) ? $buffers[$id] : '') . $data;
+		while (isset($buffers[$id]) && ($frame = decode($buffers[$id])) !== false) {
+			$buffers[$id] = substr($buffers[$id], $frame['length']);
+			on_message($connect, $frame); // Responding to incoming message
+		}
 	}
 }
 
@@ -84,10 +99,10 @@ function on_close($connect) {
 }
 
 // Handling incoming message
-function on_message($connect, $data) {
+function on_message($connect, $frame) {
 	global $approved, $waitlist, $ties;
 	$response = RXTX_OK_FLAG;
-	$txt = decode($data)['payload'];
+	$txt = $frame['payload'];
 	$flag = $txt[0];
 	$body = substr($txt, 1);
 	echo("Got '" . $txt . "'\n");
@@ -164,6 +179,37 @@ function on_message($connect, $data) {
 				return;
 			}
 			break;
+		case RXTX_STATUS_FLAG:
+			if (in_array($connect, $approved)) {
+				$user_name = array_search($connect, $approved);
+				$peer = tie_peer($user_name);
+				if ($peer !== null) {
+					fwrite($approved[$peer], encode($txt));
+				}
+			} else {
+				conn_close($connect);
+				return;
+			}
+			break;
+		case RXTX_FILE_OFFER_FLAG:
+		case RXTX_FILE_ACCEPT_FLAG:
+		case RXTX_FILE_DECLINE_FLAG:
+		case RXTX_FILE_CHUNK_FLAG:
+		case RXTX_FILE_ACK_FLAG:
+		case RXTX_FILE_DONE_FLAG:
+			if (in_array($connect, $approved)) {
+				$user_name = array_search($connect, $approved);
+				$peer = tie_peer($user_name);
+				if ($peer !== null) {
+					fwrite($approved[$peer], encode($txt));
+				} else {
+					$response = RXTX_FAULT_FLAG;
+				}
+			} else {
+				conn_close($connect);
+				return;
+			}
+			break;
 		default:
 			echo("unknown command: " . $txt . "\n");
 			break;
@@ -171,6 +217,17 @@ function on_message($connect, $data) {
 	fwrite($connect, encode($response));
 }
 
+// Get the name of user's tie subject (or null if there's no tie)
+function tie_peer($user_name) {
+	global $ties;
+	if (array_key_exists($user_name, $ties)) {
+		return $ties[$user_name];
+	} else if (in_array($user_name, $ties)) {
+		return array_search($user_name, $ties);
+	}
+	return null;
+}
+
 function untie($user_name) {
 	global $ties, $approved, $waitlist;
 	if (array_key_exists($user_name, $ties)) {
@@ -187,11 +244,12 @@ function untie($user_name) {
 
 // Dropping the connection
 function conn_close($conn) {
-	global $queue, $approved, $waitlist;
+	global $queue, $buffers, $approved, $waitlist;
 	// var_dump($queue);
 	// var_dump($approved);
 	// var_dump($waitlist);
 	// echo("CLOSING...\n");
+	unset($buffers[(int)$conn]);
 	fclose($conn);
 	unset($queue[array_search($conn, $queue)]);
 	if (in_array($conn, $approved)) {
@@ -298,9 +356,13 @@ function encode($payload, $type = 'text', $masked = false) {
 	return $frame;
 }
 
+// Decode the first frame of `$data` (its length included), or return false if it's incomplete
 function decode($data) {
 	$unmaskedPayload = '';
 	$decodedData = array();
+	if (strlen($data) < 2) {
+		return false;
+	}
 	// estimate frame type:
 	$firstByteBinary = sprintf('%08b', ord($data[0]));
 	$secondByteBinary = sprintf('%08b', ord($data[1]));
@@ -309,7 +371,7 @@ function decode($data) {
 	$payloadLength = ord($data[1]) & 127;
 	// unmasked frame is received:
 	if (!$isMasked) {
-		return array('type' => '', 'payload' => '', 'error' => 'protocol error (1002)');
+		return array('type' => '', 'payload' => '', 'error' => 'protocol error (1002)', 'length' => strlen($data));
 	}
 	switch ($opcode) {
 		// text frame:
@@ -332,15 +394,21 @@ function decode($data) {
 			$decodedData['type'] = 'pong';
 			break;
 		default:
-			return array('type' => '', 'payload' => '', 'error' => 'unknown opcode (1003)');
+			return array('type' => '', 'payload' => '', 'error' => 'unknown opcode (1003)', 'length' => strlen($data));
 	}
 	if ($payloadLength === 126) {
 		$mask = substr($data, 4, 4);
 		$payloadOffset = 8;
+		if (strlen($data) < $payloadOffset) {
+			return false;
+		}
 		$dataLength = bindec(sprintf('%08b', ord($data[2])) . sprintf('%08b', ord($data[3]))) + $payloadOffset;
 	} else if ($payloadLength === 127) {
 		$mask = substr($data, 10, 4);
 		$payloadOffset = 14;
+		if (strlen($data) < $payloadOffset) {
+			return false;
+		}
 		$tmp = '';
 		for ($i = 0; $i < 8; $i++) {
 			$tmp .= sprintf('%08b', ord($data[$i + 2]));
@@ -352,6 +420,7 @@ function decode($data) {
 		$payloadOffset = 6;
 		$dataLength = $payloadLength + $payloadOffset;
 	}
+	$decodedData['length'] = $dataLength;
 	/**
 	 * We have to check for large frames here. socket_recv cuts at 1024 bytes
 	 * so if websocket-frame is > 1024 bytes we have to wait until whole
@@ -4,9 +4,96 @@ COPYRIGHT LESTER COVEY (me@lestercovey.ml),
 
 ***************************/
 
+use std::{collections::HashMap, env, fmt, ops::Deref, sync::OnceLock};
+
+/// Catalogs of languages other than English, which is built in
+const CATALOGS: &[(&str, &str)] = &[("ru", include_str!("../locales/ru.toml"))];
+
+/// Translations of the language in use (not set if it's English or has no catalog)
+static CATALOG: OnceLock<HashMap<String, String>> = OnceLock::new();
+
+/// User-facing string, shown in the language in use if it's translated and in English otherwise
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub struct Localized {
+	key: &'static str,
+	en: &'static str,
+}
+
+impl Localized {
+	pub fn get(&self) -> &'static str {
+		self.get_from(CATALOG.get())
+	}
+	fn get_from(&self, catalog: Option<&'static HashMap<String, String>>) -> &'static str {
+		catalog
+			.and_then(|catalog| catalog.get(self.key))
+			.map(String::as_str)
+			.unwrap_or(self.en)
+	}
+}
+
+impl Deref for Localized {
+	type Target = str;
+	fn deref(&self) -> &str {
+		self.get()
+	}
+}
+
+impl fmt::Display for Localized {
+	fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+		f.write_str(self.get())
+	}
+}
+
+impl PartialEq<Localized> for String {
+	fn eq(&self, other: &Localized) -> bool {
+		self == other.get()
+	}
+}
+
+/// Declare English strings, each translated by the catalog entry named after it
+macro_rules! localized {
+	($(pub const $name:ident: &str = $en:literal;)*) => {
+		$(pub const $name: Localized = Localized { key: stringify!($name), en: $en };)*
+		/// Every localized string
+		pub const ALL: &[Localized] = &[$($name),*];
+	};
+}
+
+/// Get language code of a locale like `ru_RU.UTF-8`
+fn language_of(locale: &str) -> &str {
+	locale.split(['_', '.', '@']).next().unwrap_or_default()
+}
+
+fn catalog(language: &str) -> Option<HashMap<String, String>> {
+	CATALOGS
+		.iter()
+		.find(|(name, _)| *name == language)
+		.and_then(|(_, catalog)| toml::from_str(catalog).ok())
+}
+
+/// Pick language to show strings in: `configured` one, or the one of the locale ($LC_ALL, $LC_MESSAGES or $LANG)
+pub fn init(configured: Option<&str>) {
+	let language = match configured {
+		Some(language) => language.to_string(),
+		None => match ["LC_ALL", "LC_MESSAGES", "LANG"]
+			.iter()
+			.filter_map(|var| env::var(var).ok())
+			.find(|locale| !locale.is_empty())
+		{
+			Some(locale) => language_of(&locale).to_lowercase(),
+			None => return,
+		},
+	};
+	if let Some(catalog) = catalog(&language) {
+		CATALOG.set(catalog).ok();
+	}
+}
+
 #[rustfmt::skip]
 #[allow(dead_code)]
 pub mod ui {
+	use super::Localized;
+
 	pub const LOGO: &str = 
 	"   __            __       
 	  / /  __ _____ / /____ __
@@ -14,14 +101,90 @@ pub mod ui {
 	/_//_/\\_,_/___/_/\\_\\\\_, / 
 	                   /___/  ";
 
-	pub const USAGE_INSTRUCTIONS: &str = 
-	"USAGE INSTRUCTIONS:
-	[ARROW UP] / [ARROW DOWN] – Switch between active input blocks
-	[ENTER] – Submit input
-	[F9] / [CTRL + C] – Exit";
+	pub const MESSAGES_BLOCK_TYPING: 
+		[&str; 4] = ["Typing   ", "Typing.  ", "Typing.. ", "Typing..."];
+
+	localized! {
+	pub const USAGE_INSTRUCTIONS: 
+		&str = "USAGE INSTRUCTIONS:";
+	pub const HELP_FOCUS_PREV: 
+		&str = "Switch to previous input block";
+	pub const HELP_FOCUS_NEXT: 
+		&str = "Switch to next input block";
+	pub const HELP_SUBMIT: 
+		&str = "Submit input";
+	pub const HELP_SCROLL_UP: 
+		&str = "Scroll messages up";
+	pub const HELP_SCROLL_DOWN: 
+		&str = "Scroll messages down";
+	pub const HELP_UNTIE: 
+		&str = "Untie";
+	pub const HELP_SET_STATUS: 
+		&str = "Set status message";
+	pub const HELP_EXPORT: 
+		&str = "Export conversation transcript";
+	pub const HELP_SAVE_LOG: 
+		&str = "Save job log to a file";
+	pub const HELP_TOGGLE_INVISIBLE: 
+		&str = "Show / hide invisible characters in messages";
+	pub const HELP_COMPLETE: 
+		&str = "Complete /command (type /help in message block to list them)";
+	pub const HELP_COMPOSE: 
+		&str = "Write message in external editor";
+	pub const HELP_CANCEL: 
+		&str = "Cancel status editing";
+	pub const HELP_HELP: 
+		&str = "Show / hide all key bindings";
+	pub const HELP_QUIT: 
+		&str = "Exit";
+	pub const HELP_BLOCK: 
+		&str = " Help (any key to close) ";
+	pub const KEY_FAULT_MODIFIER: 
+		&str = "Unknown key modifier";
+	pub const KEY_FAULT_NAME: 
+		&str = "Unknown key";
 
+	pub const CLI_USAGE: 
+	&str = "USAGE:
+	husky                                       Start interactive client
+	husky send --to <user> [--message <text>]   Tie with user, send message (read from stdin if omitted) and untie
+	husky listen --from <user>                  Tie with user and print incoming messages until untied
+	husky --jsonl                               Print every received frame as JSON and accept JSON commands on stdin
+	husky --replay <file> [--speed <factor>]    Replay frames recorded with --trace in the interface, without a server (0.01 to 1000 times as fast)
+OPTIONS:
+	--key <username:password>   Auth key to use in headless mode (defaults to $HUSKY_KEY)
+	--timeout <seconds>         Time to wait for the subject to tie back and for the message to be delivered (0 to wait forever, defaults to 60)
+	--trace <file>              Record every sent and received frame to file (secrets redacted)
+	--help                      Print this message";
+
+	pub const CLI_MISSING_VALUE: 
+		&str = "Missing value for";
+	pub const CLI_BAD_VALUE: 
+		&str = "Bad value";
+	pub const CLI_UNKNOWN_ARGUMENT: 
+		&str = "Unknown argument";
+	pub const CLI_MISSING_ARGUMENT: 
+		&str = "Missing required argument";
+	pub const CLI_NO_KEY: 
+		&str = "FAULT: No auth key provided (use --key or $HUSKY_KEY)";
+	pub const CLI_EMPTY_MESSAGE: 
+		&str = "FAULT: Message is empty";
+	pub const CLI_TIE_TIMEOUT: 
+		&str = "FAULT: Subject did not tie back in time";
+	pub const CLI_DELIVERY_TIMEOUT: 
+		&str = "FAULT: Message was not delivered in time";
+	pub const CLI_TRACE_FAULT: 
+		&str = "FAULT: Unable to create trace file";
+	pub const CLI_REPLAY_FAULT: 
+		&str = "FAULT: Unable to read trace file";
+	pub const REPLAY_DONE: 
+		&str = "Replay finished";
 	pub const FATAL_RUNTIME_ERROR: 
 		&str = "WARNING: FATAL";
+	pub const CRASH_REPORT_SAVED: 
+		&str = "Crash report saved to";
+	pub const CRASH_REPORT_FAULT: 
+		&str = "Unable to save crash report";
 	pub const CONNECTION_DROPPED_ERROR: 
 		&str = "Websocket connection dropped";
 	pub const MESSAGE_CORRUPTED_ERROR: 
@@ -31,43 +194,123 @@ pub mod ui {
 	pub const USERNAME_BLOCK_INACTIVE: 
 		&str = " Username ";
 	pub const USERNAME_BLOCK_ACTIVE: 
-		&str = " Username (ENTER to initiate tie) ";
+		&str = " Username ({} to initiate tie) ";
 	pub const USERNAME_BLOCK_FILL_TIED: 
 		&str = "[TIED]";
 	pub const AUTH_KEY_BLOCK_INACTIVE: 
 		&str = " Auth key ";
 	pub const AUTH_KEY_BLOCK_ACTIVE: 
-		&str = " Auth key (ENTER to submit) ";
-	pub const MESSAGES_BLOCK_TYPING: 
-		[&str; 4] = ["Typing   ", "Typing.  ", "Typing.. ", "Typing..."];
+		&str = " Auth key ({} to submit) ";
 	pub const NEW_MESSAGE_BLOCK_INACTIVE:	
 		&str = " Message ";
 	pub const NEW_MESSAGE_BLOCK_ACTIVE: 
-		&str = " Message (ENTER to send) ";
+		&str = " Message ({} to send) ";
+	pub const STATUS_BLOCK_ACTIVE: 
+		&str = " Status message ({} to set, {} to cancel) ";
+	pub const STATUS_ONLINE: 
+		&str = "online";
+	pub const STATUS_AWAY: 
+		&str = "away";
+	pub const EXPORT_DONE: 
+		&str = "Transcript saved to";
+	pub const EXPORT_FAULT: 
+		&str = "FAULT: Unable to save transcript";
+	pub const TTL_SET: 
+		&str = "Messages you send now vanish after";
+	pub const TTL_OFF: 
+		&str = "Messages you send now are kept";
+	pub const MESSAGE_VANISHES_IN: 
+		&str = "vanishes in";
+	pub const EXPORT_FAULT_UNTIED: 
+		&str = "FAULT: Nothing to export, not tied";
+	pub const COMMAND_UNKNOWN: 
+		&str = "Unknown command";
+	pub const COMMAND_MISSING_ARGUMENT: 
+		&str = "Missing argument";
+	pub const COMMAND_BAD_ARGUMENT: 
+		&str = "Bad argument";
+	pub const COMMAND_HELP: 
+		&str = "Commands";
+	pub const COMMAND_FAULT_UNTIED: 
+		&str = "Not tied, use /tie <user> first";
+	pub const FILE_OFFERED_BY: 
+		&str = "offers you";
+	pub const FILE_OFFER_HINT: 
+		&str = "/accept or /decline";
+	pub const FILE_AWAITING: 
+		&str = "Waiting for subject to accept";
+	pub const FILE_SENDING: 
+		&str = " Sending ";
+	pub const FILE_RECEIVING: 
+		&str = " Receiving ";
+	pub const FILE_SENT: 
+		&str = "File delivered and verified:";
+	pub const FILE_SENT_CORRUPTED: 
+		&str = "FAULT: File arrived corrupted:";
+	pub const FILE_SAVED: 
+		&str = "File saved to";
+	pub const FILE_CORRUPTED: 
+		&str = "FAULT: File arrived corrupted and was discarded:";
+	pub const FILE_SAVE_FAULT: 
+		&str = "FAULT: Unable to save file";
+	pub const FILE_OPEN_FAULT: 
+		&str = "FAULT: Unable to read file";
+	pub const FILE_DECLINED: 
+		&str = "File transfer declined by subject";
+	pub const FILE_CANCELLED: 
+		&str = "File transfer cancelled";
+	pub const FILE_FAULT_BUSY: 
+		&str = "Another file transfer is in progress";
+	pub const FILE_FAULT_NO_OFFER: 
+		&str = "No file offered";
+	pub const FILE_FAULT_IDLE: 
+		&str = "No file transfer to decline";
+	pub const FILE_FAULT_TOO_LARGE: 
+		&str = "File is over the size limit";
+	pub const FILE_OFFER_TOO_LARGE: 
+		&str = "Declined file over the size limit:";
+	pub const CONFIG_ERROR: 
+		&str = "Unable to load config";
+	pub const CONFIG_TIME_FORMAT_FAULT: 
+		&str = "Bad time_format";
+	pub const NOTIFY_HOOK_FAULT: 
+		&str = "Unable to run notify_command";
+	pub const EDITOR_FAULT: 
+		&str = "Unable to write message in editor";
 	pub const ENCRYPTION_KEY_BLOCK: 
 		&str = " Encryption key ";
 	pub const CHAT_STATE_UNTIED: 
 		&str = "Untied";
 	pub const CHAT_STATE_TIED_WITH: 
 		&str = "Tied with";
+	pub const MESSAGE_ALTERED: 
+		&str = "[altered]";
 	pub const CHAT_STATE_ERROR: 
 		&str = "Error";
 	pub const CHAT_STATE_LOGOUT_PROMPT: 
-		&str = " / ENTER to Log out";
+		&str = " / {} to Log out";
 	pub const CHAT_STATE_UNTIE_PROMPT: 
-		&str = " / ENTER to Untie";
+		&str = " / {} to Untie";
 	pub const CONTINUE_PROMPT: 
-		&str = "[ ENTER to continue ]";
+		&str = "[ {} to continue ]";
 	pub const ABORT_PROMPT: 
-		&str = "[ ENTER to abort ]";
+		&str = "[ {} to abort ]";
 	pub const LOG_BLOCK: 
 		&str = " Progress log ";
+	pub const LOG_SAVE_PROMPT: 
+		&str = "[ {} to save log ]";
+	pub const LOG_SAVED: 
+		&str = "Log saved to";
+	pub const LOG_SAVE_FAULT: 
+		&str = "FAULT: Unable to save log";
 	pub const TIE_BROKEN:
 		&str = "Tie broken";
 	pub const JOB_STARTING: 
 		&str = "Starting...";
 	pub const JOB_SUCCESS: 
 		&str = "SUCCESS";
+	pub const JOB_TIMEOUT: 
+		&str = "FAULT: Timed out after";
 	pub const AUTH_JOB: 
 		&str = "Authorizing...";
 	pub const AUTH_JOB_PRECONNECT: 
@@ -103,40 +346,41 @@ pub mod ui {
 	pub const TIE_JOB_FAULT_SELFTIE:
 		&str = "FAULT: Attempt to tie with self";
 	pub const TIE_JOB_FAULT_OVERTIE:
-		&str = "FAULT: Existing tie not broken";	
+		&str = "FAULT: Existing tie not broken";
+	pub const TIE_JOB_HISTORY_FAULT:
+		&str = "History won't be kept for this tie:";
+	pub const HISTORY_NO_PASSPHRASE:
+		&str = "no passphrase (set $HUSKY_HISTORY_PASSPHRASE or history_passphrase in config)";	
+	}
 }
 
-#[rustfmt::skip]
-#[allow(dead_code)]
-pub mod flags {
-	pub const TX_AUTH_FLAG: 
-		char = 'A';
-	pub const TX_DROPME_FLAG: 
-		char = 'X';
-	pub const TX_TIE_INIT_FLAG: 
-		char = 'T';
-	pub const RX_AUTH_OK_FLAG: 
-		char = 'O';
-	pub const RX_AUTH_FAULT_FLAG: 
-		char = 'D';
-	pub const RX_AUTH_FAULT_OVERAUTH_FLAG:
-		char = 'I';
-	pub const RX_TIE_OK_FLAG:
-		char = 'S';
-	pub const RX_TIE_OK_WAIT_FLAG:
-		char = 'W';
-	pub const RX_TIE_FAULT_NOUSER_FLAG:
-		char = 'N';
-	pub const RX_TIE_FAULT_SELFTIE_FLAG:
-		char = 'M';
-	pub const RX_TIE_FAULT_OVERTIE_FLAG:
-		char = 'R';
-	pub const RXTX_UNTIE_FLAG:
-		char = 'C';
-	pub const RXTX_OK_FLAG:
-		char = 'Y';
-	pub const RXTX_FAULT_FLAG: 
-		char = 'E';
-	pub const RXTX_MESSAGE_FLAG: 
-		char = 'B';
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn catalogs_only_translate_known_strings() {
+		for (language, _) in CATALOGS {
+			let catalog = catalog(language).expect("catalog doesn't parse");
+			for key in catalog.keys() {
+				assert!(ui::ALL.iter().any(|s| s.key == key), "{}: unknown string {}", language, key);
+			}
+		}
+	}
+
+	#[test]
+	fn missing_translations_fall_back_to_english() {
+		let catalog: &'static HashMap<String, String> = Box::leak(Box::new(catalog("ru").unwrap()));
+		assert_eq!(ui::STATUS_ONLINE.get_from(Some(catalog)), "в сети");
+		assert_eq!(ui::STATUS_ONLINE.get_from(None), "online");
+		let untranslated = Localized { key: "NOT_TRANSLATED", en: "Fallback" };
+		assert_eq!(untranslated.get_from(Some(catalog)), "Fallback");
+	}
+
+	#[test]
+	fn locale_language() {
+		assert_eq!(language_of("ru_RU.UTF-8"), "ru");
+		assert_eq!(language_of("en"), "en");
+		assert_eq!(language_of("C.UTF-8"), "C");
+	}
 }
@@ -0,0 +1,193 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use serde::Deserialize;
+use std::env;
+use tui::style::{Color, Modifier, Style};
+
+/// Built-in palette to draw the interface with
+#[derive(Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum ThemeName {
+	Default,
+	/// For terminals with a light background
+	Light,
+	HighContrast,
+	/// No colors at all, only text attributes
+	Monochrome,
+}
+
+/// Styles of every distinct part of the interface
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub struct Theme {
+	/// Focused input block
+	pub focus: Style,
+	/// Unfocused input block
+	pub text: Style,
+	/// Input block that can't be used right now, timestamps
+	pub dim: Style,
+	/// Messages block
+	pub messages: Style,
+	/// Text of user's own messages
+	pub own: Style,
+	/// Senders' names, one picked for every name
+	pub nicks: &'static [Style],
+	/// Marker of messages altered to be terminal-safe
+	pub altered: Style,
+	/// Progress bar and its block
+	pub gauge: Style,
+	pub gauge_label: Style,
+	/// Job window backgrounds
+	pub job_in_progress: Style,
+	pub job_ok: Style,
+	pub job_err: Style,
+	/// Job log block and its messages
+	pub log: Style,
+	pub log_success: Style,
+	pub log_warning: Style,
+	pub log_fault: Style,
+}
+
+const fn style(fg: Option<Color>, bg: Option<Color>, modifier: Modifier) -> Style {
+	Style {
+		fg,
+		bg,
+		add_modifier: modifier,
+		sub_modifier: Modifier::empty(),
+	}
+}
+
+const fn fg(color: Color) -> Style {
+	style(Some(color), None, Modifier::empty())
+}
+
+const fn bg(color: Color) -> Style {
+	style(None, Some(color), Modifier::empty())
+}
+
+const fn modifier(modifier: Modifier) -> Style {
+	style(None, None, modifier)
+}
+
+const PLAIN: Style = modifier(Modifier::empty());
+
+impl Theme {
+	pub const DEFAULT: Theme = Theme {
+		focus: fg(Color::Cyan),
+		text: PLAIN,
+		dim: fg(Color::DarkGray),
+		messages: fg(Color::Gray),
+		own: fg(Color::White),
+		nicks: &[
+			fg(Color::LightRed),
+			fg(Color::LightGreen),
+			fg(Color::LightYellow),
+			fg(Color::LightBlue),
+			fg(Color::LightMagenta),
+			fg(Color::LightCyan),
+		],
+		altered: fg(Color::Yellow),
+		gauge: fg(Color::White),
+		gauge_label: fg(Color::Black),
+		job_in_progress: bg(Color::DarkGray),
+		job_ok: bg(Color::Green),
+		job_err: bg(Color::Red),
+		log: style(Some(Color::White), Some(Color::Black), Modifier::empty()),
+		log_success: fg(Color::LightGreen),
+		log_warning: fg(Color::Yellow),
+		log_fault: fg(Color::LightRed),
+	};
+	pub const LIGHT: Theme = Theme {
+		focus: fg(Color::Blue),
+		text: PLAIN,
+		dim: fg(Color::Gray),
+		messages: fg(Color::Black),
+		own: fg(Color::DarkGray),
+		nicks: &[
+			fg(Color::Red),
+			fg(Color::Green),
+			fg(Color::Blue),
+			fg(Color::Magenta),
+			fg(Color::Cyan),
+		],
+		altered: fg(Color::Magenta),
+		gauge: fg(Color::Blue),
+		gauge_label: fg(Color::White),
+		job_in_progress: bg(Color::Gray),
+		job_ok: bg(Color::LightGreen),
+		job_err: bg(Color::LightRed),
+		log: style(Some(Color::Black), Some(Color::White), Modifier::empty()),
+		log_success: fg(Color::Green),
+		log_warning: fg(Color::Magenta),
+		log_fault: fg(Color::Red),
+	};
+	pub const HIGH_CONTRAST: Theme = Theme {
+		focus: style(Some(Color::Yellow), None, Modifier::BOLD),
+		text: fg(Color::White),
+		dim: fg(Color::Gray),
+		messages: fg(Color::White),
+		own: style(Some(Color::White), None, Modifier::BOLD),
+		nicks: &[
+			style(Some(Color::LightRed), None, Modifier::BOLD),
+			style(Some(Color::LightGreen), None, Modifier::BOLD),
+			style(Some(Color::LightYellow), None, Modifier::BOLD),
+			style(Some(Color::LightMagenta), None, Modifier::BOLD),
+			style(Some(Color::LightCyan), None, Modifier::BOLD),
+		],
+		altered: style(Some(Color::Black), Some(Color::Yellow), Modifier::BOLD),
+		gauge: fg(Color::White),
+		gauge_label: style(Some(Color::Black), None, Modifier::BOLD),
+		job_in_progress: style(Some(Color::White), Some(Color::Black), Modifier::empty()),
+		job_ok: style(Some(Color::Black), Some(Color::Green), Modifier::BOLD),
+		job_err: style(Some(Color::White), Some(Color::Red), Modifier::BOLD),
+		log: style(Some(Color::White), Some(Color::Black), Modifier::empty()),
+		log_success: style(Some(Color::LightGreen), None, Modifier::BOLD),
+		log_warning: style(Some(Color::Yellow), None, Modifier::BOLD),
+		log_fault: style(Some(Color::LightRed), None, Modifier::BOLD),
+	};
+	pub const MONOCHROME: Theme = Theme {
+		focus: modifier(Modifier::BOLD),
+		text: PLAIN,
+		dim: modifier(Modifier::DIM),
+		messages: PLAIN,
+		own: modifier(Modifier::ITALIC),
+		nicks: &[modifier(Modifier::BOLD), modifier(Modifier::UNDERLINED)],
+		altered: modifier(Modifier::REVERSED),
+		gauge: PLAIN,
+		gauge_label: modifier(Modifier::REVERSED),
+		job_in_progress: PLAIN,
+		job_ok: PLAIN,
+		job_err: modifier(Modifier::BOLD),
+		log: PLAIN,
+		log_success: PLAIN,
+		log_warning: modifier(Modifier::UNDERLINED),
+		log_fault: modifier(Modifier::BOLD),
+	};
+
+	pub fn of(name: ThemeName) -> Theme {
+		match name {
+			ThemeName::Default => Theme::DEFAULT,
+			ThemeName::Light => Theme::LIGHT,
+			ThemeName::HighContrast => Theme::HIGH_CONTRAST,
+			ThemeName::Monochrome => Theme::MONOCHROME,
+		}
+	}
+	/// Get style of sender's name, which is always the same for the same name
+	pub fn nick(&self, name: &str) -> Style {
+		// FNV-1a, as std hashers aren't guaranteed to stay the same between releases
+		let hash = name
+			.bytes()
+			.fold(0xcbf29ce484222325u64, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3));
+		self.nicks[(hash % self.nicks.len() as u64) as usize]
+	}
+	/// Get theme to draw with, which is always monochrome if `$NO_COLOR` is set (see https://no-color.org)
+	pub fn load(name: ThemeName) -> Theme {
+		match env::var_os("NO_COLOR") {
+			Some(no_color) if !no_color.is_empty() => Theme::MONOCHROME,
+			_ => Theme::of(name),
+		}
+	}
+}
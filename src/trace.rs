@@ -0,0 +1,133 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use crate::flags::*;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+#[cfg(unix)]
+use std::os::unix::fs::OpenOptionsExt;
+use std::{
+	fs,
+	io::{self, Write},
+	path::Path,
+	sync::{Arc, Mutex},
+};
+
+/// What secrets are replaced with in traces
+pub const REDACTED: &str = "[REDACTED]";
+
+#[derive(Serialize, Deserialize, PartialEq, Clone, Copy, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+	/// Sent by the client
+	Tx,
+	/// Received from the server
+	Rx,
+}
+
+/// A single traced frame, stored as one JSON line
+#[derive(Serialize, Deserialize, PartialEq, Clone, Debug)]
+pub struct TraceRecord {
+	pub time: DateTime<Utc>,
+	pub direction: Direction,
+	pub frame: String,
+}
+
+/// Sink every frame going through a [`Client`](crate::Client) is recorded to. Cheap to clone
+#[derive(Clone)]
+pub struct Trace {
+	out: Arc<Mutex<Box<dyn Write + Send>>>,
+}
+
+impl Trace {
+	/// Trace into a file, truncating it
+	pub fn create(path: &Path) -> io::Result<Trace> {
+		let mut options = fs::OpenOptions::new();
+		options.write(true).create(true).truncate(true);
+		// Frames carry messages and usernames, so nobody else gets to read them
+		#[cfg(unix)]
+		options.mode(0o600);
+		Ok(Trace::new(options.open(path)?))
+	}
+	pub fn new(out: impl Write + Send + 'static) -> Trace {
+		Trace {
+			out: Arc::new(Mutex::new(Box::new(out))),
+		}
+	}
+	/// Write frame down (secrets redacted). Tracing must never break the connection, so failures are ignored
+	pub fn record(&self, direction: Direction, frame: &str) {
+		let record = TraceRecord {
+			time: Utc::now(),
+			direction,
+			frame: match direction {
+				Direction::Tx => redact(frame),
+				Direction::Rx => frame.to_string(),
+			},
+		};
+		if let (Ok(mut out), Ok(line)) = (self.out.lock(), serde_json::to_string(&record)) {
+			writeln!(out, "{}", line).ok();
+			out.flush().ok();
+		}
+	}
+}
+
+/// Read trace written by [`Trace`], oldest frame first
+pub fn load(path: &Path) -> io::Result<Vec<TraceRecord>> {
+	fs::read_to_string(path)?
+		.lines()
+		.enumerate()
+		.filter(|(_, line)| !line.trim().is_empty())
+		.map(|(i, line)| {
+			serde_json::from_str(line).map_err(|err| {
+				io::Error::new(io::ErrorKind::InvalidData, format!("line {}: {}", i + 1, err))
+			})
+		})
+		.collect()
+}
+
+/// Hide server key and password of an outgoing frame, keeping everything else intact
+pub fn redact(frame: &str) -> String {
+	match frame.strip_prefix(TX_AUTH_FLAG) {
+		Some(body) => {
+			let username = body
+				.split_once('/')
+				.and_then(|(_, user_key)| user_key.split(':').next())
+				.unwrap_or_default();
+			format!("{}{}/{}:{}", TX_AUTH_FLAG, REDACTED, username, REDACTED)
+		}
+		None => frame.to_string(),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn trace_is_loaded_back() {
+		let path = std::env::temp_dir().join(format!("husky-trace-{}.jsonl", std::process::id()));
+		let trace = Trace::create(&path).unwrap();
+		trace.record(Direction::Tx, "Tbob");
+		trace.record(Direction::Rx, "W");
+		let records = load(&path).unwrap();
+		assert_eq!(records.len(), 2);
+		assert_eq!((records[0].direction, records[0].frame.as_str()), (Direction::Tx, "Tbob"));
+		assert_eq!((records[1].direction, records[1].frame.as_str()), (Direction::Rx, "W"));
+		fs::write(&path, "{}\n").unwrap();
+		assert_eq!(load(&path).unwrap_err().kind(), io::ErrorKind::InvalidData);
+		fs::remove_file(&path).ok();
+	}
+
+	#[test]
+	fn only_secrets_are_redacted() {
+		assert_eq!(
+			redact("As3cr3t/alice:wonderland"),
+			"A[REDACTED]/alice:[REDACTED]"
+		);
+		assert_eq!(redact("A"), "A[REDACTED]/:[REDACTED]");
+		assert_eq!(redact("Balice: my password is A"), "Balice: my password is A");
+	}
+}
@@ -0,0 +1,203 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+use crate::protocol::FileOffer;
+use sha2::{Digest, Sha256};
+use std::{
+	fs, io,
+	path::{Path, PathBuf},
+};
+
+/// Bytes of file carried by a single chunk frame (before base64)
+pub const CHUNK_SIZE: usize = 8 * 1024;
+
+fn sha256_hex(data: &[u8]) -> String {
+	Sha256::digest(data)
+		.iter()
+		.map(|b| format!("{:02x}", b))
+		.collect()
+}
+
+fn percent(done: u64, total: u64) -> u16 {
+	// Empty files are done as soon as they start
+	(done * 100).checked_div(total).unwrap_or(100) as u16
+}
+
+/// File being sent. The next chunk should only be sent once the previous one is acknowledged
+#[derive(PartialEq, Clone, Debug)]
+pub struct Upload {
+	offer: FileOffer,
+	data: Vec<u8>,
+	sent: usize,
+	started: bool,
+}
+
+impl Upload {
+	/// Read the whole file to be offered
+	pub fn open(path: &Path) -> io::Result<Upload> {
+		let data = fs::read(path)?;
+		let name = path
+			.file_name()
+			.map(|n| n.to_string_lossy().to_string())
+			.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "not a file"))?;
+		Ok(Upload {
+			offer: FileOffer {
+				name,
+				size: data.len() as u64,
+				sha256: sha256_hex(&data),
+			},
+			data,
+			sent: 0,
+			started: false,
+		})
+	}
+	pub fn offer(&self) -> &FileOffer {
+		&self.offer
+	}
+	/// Take the next chunk to send (`None` once everything is sent). Empty files still take one empty chunk
+	pub fn next_chunk(&mut self) -> Option<&[u8]> {
+		if self.started && self.sent >= self.data.len() {
+			return None;
+		}
+		self.started = true;
+		let from = self.sent;
+		self.sent = (from + CHUNK_SIZE).min(self.data.len());
+		Some(&self.data[from..self.sent])
+	}
+	/// Percentage of the file sent so far
+	pub fn progress(&self) -> u16 {
+		percent(self.sent as u64, self.offer.size)
+	}
+}
+
+/// File being received
+#[derive(PartialEq, Clone, Debug)]
+pub struct Download {
+	offer: FileOffer,
+	data: Vec<u8>,
+	/// Whether more data than offered arrived (which is never kept)
+	overflowed: bool,
+}
+
+impl Download {
+	pub fn new(offer: FileOffer) -> Download {
+		Download {
+			offer,
+			data: Vec::new(),
+			overflowed: false,
+		}
+	}
+	pub fn offer(&self) -> &FileOffer {
+		&self.offer
+	}
+	/// Add received chunk. Returns `true` once the whole file is here,
+	/// or once more than offered arrives (such a file never passes verification)
+	pub fn push(&mut self, chunk: &[u8]) -> bool {
+		if self.data.len() as u64 + chunk.len() as u64 > self.offer.size {
+			self.overflowed = true;
+			return true;
+		}
+		self.data.extend_from_slice(chunk);
+		self.data.len() as u64 >= self.offer.size
+	}
+	/// Percentage of the file received so far
+	pub fn progress(&self) -> u16 {
+		percent(self.data.len() as u64, self.offer.size).min(100)
+	}
+	/// Whether received contents match the offer
+	pub fn verify(&self) -> bool {
+		!self.overflowed && self.data.len() as u64 == self.offer.size && sha256_hex(&self.data) == self.offer.sha256
+	}
+	/// Write received file into `dir`, never overwriting anything that's already there
+	pub fn save(&self, dir: &Path) -> io::Result<PathBuf> {
+		// The name comes from the other side, so only its last component is trusted
+		let name = Path::new(&self.offer.name)
+			.file_name()
+			.map(|n| n.to_string_lossy().to_string())
+			.unwrap_or_else(|| "file".to_string());
+		let (stem, extension) = match name.rsplit_once('.') {
+			Some((stem, extension)) if !stem.is_empty() => (stem.to_string(), format!(".{}", extension)),
+			_ => (name.clone(), String::new()),
+		};
+		fs::create_dir_all(dir)?;
+		let mut path = dir.join(&name);
+		let mut n = 1;
+		loop {
+			match fs::OpenOptions::new().write(true).create_new(true).open(&path) {
+				Ok(mut file) => {
+					io::Write::write_all(&mut file, &self.data)?;
+					return Ok(path);
+				}
+				Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+					path = dir.join(format!("{} ({}){}", stem, n, extension));
+					n += 1;
+				}
+				Err(err) => return Err(err),
+			}
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn round_trip() {
+		let dir = std::env::temp_dir().join(format!("husky-transfer-round-trip-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let data: Vec<u8> = (0..CHUNK_SIZE * 2 + 10).map(|i| i as u8).collect();
+		fs::write(dir.join("log.txt"), &data).unwrap();
+		let mut upload = Upload::open(&dir.join("log.txt")).unwrap();
+		let mut download = Download::new(FileOffer::decode(&upload.offer().encode()[1..]).unwrap());
+		let mut chunks = 0;
+		while let Some(chunk) = upload.next_chunk() {
+			chunks += 1;
+			assert_eq!(download.push(chunk), upload.progress() == 100);
+		}
+		assert_eq!(chunks, 3);
+		assert!(download.verify());
+		let saved = download.save(&dir).unwrap();
+		assert_eq!(saved, dir.join("log (1).txt"));
+		assert_eq!(fs::read(saved).unwrap(), data);
+		fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn empty_file_takes_one_chunk() {
+		let dir = std::env::temp_dir().join(format!("husky-transfer-empty-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		fs::write(dir.join("empty"), b"").unwrap();
+		let mut upload = Upload::open(&dir.join("empty")).unwrap();
+		assert_eq!(upload.next_chunk(), Some(&[][..]));
+		assert_eq!(upload.next_chunk(), None);
+		fs::remove_dir_all(&dir).ok();
+	}
+
+	#[test]
+	fn corrupted_and_hostile_files() {
+		let dir = std::env::temp_dir().join(format!("husky-transfer-hostile-{}", std::process::id()));
+		fs::create_dir_all(&dir).unwrap();
+		let mut download = Download::new(FileOffer {
+			name: "../../.bashrc".to_string(),
+			size: 3,
+			sha256: sha256_hex(b"abc"),
+		});
+		assert!(download.push(b"abd"));
+		assert!(!download.verify());
+		let mut oversized = Download::new(FileOffer {
+			name: "small.txt".to_string(),
+			size: 3,
+			sha256: sha256_hex(b"abc"),
+		});
+		assert!(!oversized.push(b"ab"));
+		assert!(oversized.push(b"cd"));
+		assert_eq!(oversized.data, b"ab");
+		assert!(!oversized.verify());
+		assert_eq!(download.save(&dir).unwrap(), dir.join(".bashrc"));
+		fs::remove_dir_all(&dir).ok();
+	}
+}
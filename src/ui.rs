@@ -0,0 +1,980 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2021
+
+***************************/
+
+use crate::strings::ui::*;
+use crate::log::LogLevel;
+use crate::keymap::Action;
+use crate::theme::Theme;
+use crate::{files, status_describe, App, AppState, Chat, ChatMessage, ChatState, JobState, MessageState};
+use chrono::Utc;
+use std::time::Duration;
+use tui::{
+	backend::Backend,
+	layout::{Alignment, Constraint, Corner, Direction, Layout, Rect},
+	style::Style,
+	text::{Span, Spans},
+	widgets::{Block, BorderType, Borders, Clear, Gauge, List, ListItem, Paragraph},
+	Frame,
+};
+use unicode_width::UnicodeWidthStr;
+
+/// Part of chat UI that can be clicked
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum ChatTarget {
+	Header,
+	/// Untie or log out prompt, shown in header while it's focused
+	HeaderHint,
+	Subject,
+	EncryptionKey,
+	Messages,
+	NewMessage,
+}
+
+/// Split screen into chat blocks: header, subject, encryption key, messages and new message
+fn chat_layout(area: Rect) -> Vec<Rect> {
+	Layout::default()
+		.direction(Direction::Vertical)
+		.constraints(
+			[
+				Constraint::Length(2),
+				Constraint::Length(3),
+				Constraint::Length(3),
+				Constraint::Min(1),
+				Constraint::Length(3),
+			]
+			.as_ref(),
+		)
+		.split(area)
+}
+
+/// Get chat header text and the prompt following it
+fn chat_header(app: &App) -> (String, String) {
+	let (tied, cs) = match &app.state {
+		AppState::Chat(Chat {
+			state: ChatState::Tied(a),
+		}) => (
+			true,
+			match &app.subject_status {
+				Some(status) => format!("{} {} [{}]", CHAT_STATE_TIED_WITH, a, status_describe(status)),
+				None => format!("{} {}", CHAT_STATE_TIED_WITH, a),
+			},
+		),
+		_ => (false, CHAT_STATE_UNTIED.to_string()),
+	};
+	let hint = if app.input_focus == 0 {
+		let prompt = if tied {
+			CHAT_STATE_UNTIE_PROMPT
+		} else {
+			CHAT_STATE_LOGOUT_PROMPT
+		};
+		app.keymap.prompt(&prompt, &[Action::Submit])
+	} else {
+		String::new()
+	};
+	let header = format!(
+		"Husky v{} / {} [{}] / {}",
+		env!("CARGO_PKG_VERSION"),
+		app.user_key.as_ref().unwrap().username,
+		status_describe(&app.status),
+		cs
+	);
+	(header, hint)
+}
+
+/// Find what's under the cell clicked in chat UI drawn on `area`
+pub fn chat_target(app: &App, area: Rect, column: u16, row: u16) -> Option<ChatTarget> {
+	let chunks = chat_layout(area);
+	let index = chunks.iter().position(|chunk| {
+		(chunk.left()..chunk.right()).contains(&column) && (chunk.top()..chunk.bottom()).contains(&row)
+	})?;
+	Some(match index {
+		0 => {
+			let (header, hint) = chat_header(app);
+			let hint_start = chunks[0].x + header.width() as u16;
+			if row == chunks[0].y && (hint_start..hint_start + hint.width() as u16).contains(&column) {
+				ChatTarget::HeaderHint
+			} else {
+				ChatTarget::Header
+			}
+		}
+		1 => ChatTarget::Subject,
+		2 => ChatTarget::EncryptionKey,
+		3 => ChatTarget::Messages,
+		_ => ChatTarget::NewMessage,
+	})
+}
+
+/// Format time left as two largest units, like `1m 30s`
+pub fn countdown(remaining: Duration) -> String {
+	let secs = remaining.as_secs();
+	let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+	match (hours, minutes, seconds) {
+		(0, 0, s) => format!("{}s", s),
+		(0, m, 0) => format!("{}m", m),
+		(0, m, s) => format!("{}m {}s", m, s),
+		(h, 0, _) => format!("{}h", h),
+		(h, m, _) => format!("{}h {}m", h, m),
+	}
+}
+
+/// Renders UI of app's current state
+pub fn draw<B: Backend>(f: &mut Frame<B>, app: &App) {
+	match app.state {
+		AppState::Auth => auth_ui(f, app),
+		AppState::Chat(_) => chat_ui(f, app),
+		AppState::Job(_) => job_ui(f, app),
+	}
+	if app.help_shown {
+		help_ui(f, app)
+	}
+}
+
+/// Renders key bindings over the current state UI
+fn help_ui<B: Backend>(f: &mut Frame<B>, app: &App) {
+	let help = app.keymap.help();
+	let size = f.size();
+	let width = (help.lines().map(|l| l.width() as u16).max().unwrap_or(0) + 8).min(size.width);
+	let height = (help.lines().count() as u16 + 2).min(size.height);
+	let area = Rect::new(
+		(size.width - width) / 2,
+		(size.height - height) / 2,
+		width,
+		height,
+	);
+	let help = Paragraph::new(help).block(
+		Block::default()
+			.borders(Borders::ALL)
+			.border_type(BorderType::Thick)
+			.style(app.theme.focus)
+			.title(HELP_BLOCK.get()),
+	);
+	f.render_widget(Clear, area);
+	f.render_widget(help, area);
+}
+
+/// Progress bar the way jobs show it
+fn progress_gauge(theme: &Theme, progress: u16, title: String) -> Gauge<'static> {
+	Gauge::default()
+		.gauge_style(theme.gauge)
+		.percent(progress)
+		.label(Span::styled(format!("{}%", progress), theme.gauge_label))
+		.block(
+			Block::default()
+				.borders(Borders::ALL)
+				.style(theme.gauge)
+				.title(title),
+		)
+}
+
+/// Style of job log message of given level
+fn log_level_style(theme: &Theme, level: LogLevel) -> Style {
+	match level {
+		LogLevel::Info => Style::default(),
+		LogLevel::Success => theme.log_success,
+		LogLevel::Warning => theme.log_warning,
+		LogLevel::Fault => theme.log_fault,
+	}
+}
+
+/// Renders app's `Job` state UI
+fn job_ui<B: Backend>(f: &mut Frame<B>, app: &App) {
+	// TODO: Typing indicator
+	// if app.typing_state_iteration >= 3 {
+	// 	app.typing_state_iteration = 0
+	// } else {
+	// 	app.typing_state_iteration += 1
+	// }
+	// ( .title(strings::MESSAGES_BLOCK_TYPING[app.typing_state_iteration as usize]) )
+	match &app.state {
+		AppState::Job(job) => {
+			// FIXME:
+			// Shotgun approach (aimed to fix #2)
+			let progress = job.progress;
+			if progress > 100 {
+				return;
+			};
+			let chunks = Layout::default()
+				.direction(Direction::Vertical)
+				.vertical_margin(2)
+				.horizontal_margin(12)
+				.constraints([Constraint::Min(1)].as_ref())
+				.split(f.size());
+			let main_window = Block::default()
+				.borders(Borders::NONE)
+				.title(job.title.clone())
+				.title_alignment(Alignment::Center)
+				.style(match job.state {
+					JobState::InProgress(_) => app.theme.job_in_progress,
+					JobState::Ok(_) => app.theme.job_ok,
+					JobState::Err(_) => app.theme.job_err,
+				});
+			f.render_widget(main_window, chunks[0]);
+			{
+				let chunks = Layout::default()
+					.direction(Direction::Vertical)
+					.vertical_margin(2)
+					.horizontal_margin(4)
+					.constraints(
+						[
+							Constraint::Length(3),
+							Constraint::Min(1),
+							Constraint::Length(1),
+							Constraint::Length(1),
+						]
+						.as_ref(),
+					)
+					.split(chunks[0]);
+				f.render_widget(progress_gauge(&app.theme, progress, String::new()), chunks[0]);
+				let log_messages: Vec<ListItem> = app
+					.job_log
+					.iter()
+					.skip(app.scroll)
+					.map(|entry| {
+						let content = vec![Spans::from(vec![
+							Span::styled(entry.time_render(), app.theme.dim),
+							Span::raw(" "),
+							Span::styled(entry.message.clone(), log_level_style(&app.theme, entry.level)),
+						])];
+						ListItem::new(content)
+					})
+					.collect();
+				// Log has its own background so that faults stay readable on the red window
+				let log = List::new(log_messages).block(
+					Block::default()
+						.borders(Borders::ALL)
+						.style(app.theme.log)
+						.title(LOG_BLOCK.get())
+						.title_alignment(Alignment::Center),
+				);
+				f.render_widget(log, chunks[1]);
+				let save_prompt = app.keymap.prompt(&LOG_SAVE_PROMPT, &[Action::SaveLog]);
+				let prompt = match &job.state {
+					JobState::InProgress(Some(_)) => {
+						format!("{}  {}", app.keymap.prompt(&ABORT_PROMPT, &[Action::Submit]), save_prompt)
+					}
+					JobState::InProgress(None) => save_prompt,
+					_ => format!("{}  {}", app.keymap.prompt(&CONTINUE_PROMPT, &[Action::Submit]), save_prompt),
+				};
+				f.render_widget(Paragraph::new(prompt).alignment(Alignment::Center), chunks[3]);
+			}
+		}
+		_ => {
+			// TODO:
+			// More descriptive errors (maybe)
+			panic!("{}", FATAL_RUNTIME_ERROR);
+		}
+	}
+}
+
+/// Renders app's `Auth` state UI
+fn auth_ui<B: Backend>(f: &mut Frame<B>, app: &App) {
+	let instructions = app.keymap.help_for(&[
+		Action::FocusPrev,
+		Action::FocusNext,
+		Action::Submit,
+		Action::Help,
+		Action::Quit,
+	]);
+	let chunks = Layout::default()
+		.direction(Direction::Vertical)
+		.margin(1)
+		.constraints(
+			[
+				Constraint::Length(6),
+				Constraint::Length(3),
+				Constraint::Min(1),
+				Constraint::Length(instructions.lines().count() as u16),
+			]
+			.as_ref(),
+		)
+		.split(f.size());
+	let header = Paragraph::new(format!(
+		"{}v{} ({})",
+		LOGO,
+		env!("CARGO_PKG_VERSION"),
+		app.server.name
+	))
+	.style(if app.input_focus == 0 {
+		app.theme.focus
+	} else {
+		app.theme.text
+	});
+	f.render_widget(header, chunks[0]);
+	let input = Paragraph::new(app.inputs[0].as_ref())
+		.style(if app.input_focus == 1 {
+			app.theme.focus
+		} else {
+			app.theme.text
+		})
+		.block(
+			Block::default()
+				.borders(Borders::ALL)
+				.title(if app.input_focus == 1 {
+					app.keymap.prompt(&AUTH_KEY_BLOCK_ACTIVE, &[Action::Submit])
+				} else {
+					AUTH_KEY_BLOCK_INACTIVE.to_string()
+				})
+				.border_type(if app.input_focus == 1 {
+					BorderType::Thick
+				} else {
+					BorderType::Double
+				}),
+		);
+	f.render_widget(input.clone(), chunks[1]);
+	let instructions = Paragraph::new(instructions);
+	f.render_widget(instructions, chunks[3]);
+	if app.input_focus == 1 {
+		f.set_cursor(
+			chunks[1].x + app.inputs[0].width() as u16 + 1,
+			chunks[1].y + 1,
+		)
+	}
+}
+
+/// Renders app's `Chat` state UI
+fn chat_ui<B: Backend>(f: &mut Frame<B>, app: &App) {
+	match &app.state {
+		AppState::Chat(chat) => {
+			let tied = matches!(chat.state, ChatState::Tied(_));
+			let chunks = chat_layout(f.size());
+			let (header, hint) = chat_header(app);
+			let header = Paragraph::new(format!("{}{}", header, hint)).style(if app.input_focus == 0 {
+				app.theme.focus
+			} else {
+				app.theme.text
+			});
+			f.render_widget(header, chunks[0]);
+			let in_focus = app.input_focus == 1;
+			let subject_input = Paragraph::new(if !in_focus && tied {
+				USERNAME_BLOCK_FILL_TIED.get()
+			} else {
+				app.inputs[0].as_ref()
+			})
+			.style(if in_focus {
+				app.theme.focus
+			} else {
+				app.theme.text
+			})
+			.block(
+				Block::default()
+					.borders(Borders::ALL)
+					.title(if in_focus {
+						app.keymap.prompt(&USERNAME_BLOCK_ACTIVE, &[Action::Submit])
+					} else {
+						USERNAME_BLOCK_INACTIVE.to_string()
+					})
+					.border_type(if in_focus {
+						BorderType::Thick
+					} else {
+						BorderType::Double
+					}),
+			);
+			f.render_widget(subject_input, chunks[1]);
+			let encryption_key_input = Paragraph::new(app.inputs[1].as_ref())
+				.style(match app.input_focus {
+					2 => app.theme.focus,
+					_ => {
+						if tied {
+							app.theme.text
+						} else {
+							app.theme.dim
+						}
+					}
+				})
+				.block(
+					Block::default()
+						.borders(Borders::ALL)
+						.title(ENCRYPTION_KEY_BLOCK.get())
+						.border_type(match app.input_focus {
+							2 => BorderType::Thick,
+							_ => BorderType::Double,
+						}),
+				);
+			f.render_widget(encryption_key_input, chunks[2]);
+			let shown: Vec<&ChatMessage> = app.chat_messages.iter().skip(app.scroll).collect();
+			let mut messages: Vec<ListItem> = Vec::new();
+			for (i, m) in shown.iter().enumerate() {
+				let (sender, sender_altered) = husky::sanitize(&m.sender, app.show_invisible);
+				let (text, text_altered) = husky::sanitize(&m.text, app.show_invisible);
+				let mut content = vec![Span::raw(m.stamp(&app.config.time_format)), Span::raw(" ")];
+				if !sender.is_empty() {
+					content.push(Span::styled(sender, app.theme.nick(&m.sender)));
+					content.push(Span::raw(": "));
+				}
+				content.push(Span::styled(
+					text,
+					match m.state {
+						MessageState::Pending => app.theme.dim,
+						_ if m.is_own() => app.theme.own,
+						_ => Style::default(),
+					},
+				));
+				if sender_altered || text_altered {
+					// Let the user know the text isn't exactly what was sent
+					content.push(Span::raw(" "));
+					content.push(Span::styled(MESSAGE_ALTERED.get(), app.theme.altered));
+				}
+				if let Some(expires) = m.expires {
+					// Rounded up, so that nothing is shown as having 0s left
+					let remaining = (expires - Utc::now()).num_milliseconds().max(0) as u64;
+					content.push(Span::styled(
+						format!(" [{} {}]", MESSAGE_VANISHES_IN, countdown(Duration::from_millis(remaining + 999))),
+						app.theme.dim,
+					));
+				}
+				messages.push(ListItem::new(Spans::from(content)));
+				// Messages go newest first, so a day is headed by the item after its earliest message
+				if shown.get(i + 1).is_none_or(|next| next.day() != m.day()) {
+					messages.push(ListItem::new(Span::styled(
+						format!("── {} ──", m.day().format("%Y-%m-%d")),
+						app.theme.dim,
+					)));
+				}
+			}
+			let messages = List::new(messages)
+				.block(
+					Block::default()
+						.style(app.theme.messages)
+						.borders(Borders::ALL)
+						.title(match app.chat_notice.clone().or_else(|| files::offer_prompt(app)) {
+							Some(notice) => format!(" {} ", notice),
+							None => String::new(),
+						}),
+				)
+				.start_corner(Corner::BottomLeft);
+			match app.transfer.as_ref().and_then(|t| t.progress()) {
+				Some((title, progress)) => {
+					let chunks = Layout::default()
+						.direction(Direction::Vertical)
+						.constraints([Constraint::Min(1), Constraint::Length(3)].as_ref())
+						.split(chunks[3]);
+					f.render_widget(messages, chunks[0]);
+					f.render_widget(progress_gauge(&app.theme, progress, title), chunks[1]);
+				}
+				None => f.render_widget(messages, chunks[3]),
+			}
+			let new_message_input = Paragraph::new(app.inputs[2].as_ref())
+				.style(match app.input_focus {
+					3 => app.theme.focus,
+					_ => {
+						if tied {
+							app.theme.text
+						} else {
+							app.theme.dim
+						}
+					}
+				})
+				.block(
+					Block::default()
+						.borders(Borders::ALL)
+						.title(match app.input_focus {
+							3 if app.status_editing => {
+								app.keymap.prompt(&STATUS_BLOCK_ACTIVE, &[Action::Submit, Action::Cancel])
+							}
+							3 => app.keymap.prompt(&NEW_MESSAGE_BLOCK_ACTIVE, &[Action::Submit]),
+							_ => NEW_MESSAGE_BLOCK_INACTIVE.to_string(),
+						})
+						.border_type(match app.input_focus {
+							3 => BorderType::Thick,
+							_ => BorderType::Double,
+						}),
+				);
+			f.render_widget(new_message_input, chunks[4]);
+			if app.input_focus != 0 {
+				f.set_cursor(
+					chunks[(app.input_focus) as usize].x
+						+ app.inputs[(app.input_focus - 1) as usize].width() as u16
+						+ 1,
+					chunks[(if app.input_focus == 3 {
+						4
+					} else {
+						app.input_focus
+					}) as usize]
+						.y + 1,
+				)
+			}
+		}
+		_ => {
+			panic!("{}", FATAL_RUNTIME_ERROR);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use crate::log::LogEntry;
+	use crate::config::Config;
+	use crate::{Job, JobSwitchAppState};
+	use chrono::{TimeZone, Utc};
+	use std::{collections::BTreeMap, convert::TryFrom};
+	use husky::{Download, FileOffer, Presence, Status, UserKey};
+	use crate::keymap::{Key, Keymap};
+	use crate::theme::Theme;
+	use tui::{backend::TestBackend, style::{Color, Modifier}, Terminal};
+
+	fn app(state: AppState, input_focus: u8) -> App {
+		let mut app = App::null();
+		app.config = Config::default();
+		app.keymap = Keymap::new(&BTreeMap::new());
+		app.server.name = "Test server".to_string();
+		app.user_key = Some(UserKey::new("alice:wonderland".to_string()));
+		app.max_input_focus = match state {
+			AppState::Chat(_) => 3,
+			_ => 1,
+		};
+		app.input_focus = input_focus;
+		app.state = state;
+		app
+	}
+
+	fn tied() -> AppState {
+		AppState::Chat(Chat::with_subject("bob".to_string()))
+	}
+
+	fn job(state: JobState, progress: u16) -> AppState {
+		AppState::Job(Job {
+			title: TIE_JOB.to_string(),
+			progress,
+			state,
+			data: vec!["bob".to_string()],
+		})
+	}
+
+	fn message(sender: &str, text: &str, state: MessageState) -> ChatMessage {
+		ChatMessage {
+			time: Utc.with_ymd_and_hms(2022, 5, 1, 12, 30, 0).unwrap(),
+			sender: sender.to_string(),
+			text: text.to_string(),
+			state,
+			expires: None,
+		}
+	}
+
+	/// Draw app into a test terminal, returning its buffer
+	fn render(app: &App, width: u16, height: u16) -> Terminal<TestBackend> {
+		let mut terminal = Terminal::new(TestBackend::new(width, height)).unwrap();
+		terminal.draw(|f| draw(f, app)).unwrap();
+		terminal
+	}
+
+	fn text(terminal: &Terminal<TestBackend>) -> String {
+		let buffer = terminal.backend().buffer();
+		let mut out = String::new();
+		for y in 0..buffer.area.height {
+			for x in 0..buffer.area.width {
+				out.push_str(&buffer.get(x, y).symbol);
+			}
+			out.push('\n');
+		}
+		out
+	}
+
+	/// Snapshot everything drawn, with the parts that change from build to build (or zone to zone) masked
+	macro_rules! assert_ui {
+		($name:expr, $app:expr, $width:expr, $height:expr) => {
+			let terminal = render(&$app, $width, $height);
+			insta::with_settings!({filters => vec![
+				(r"v\d+\.\d+\.\d+\S*", "v[version]"),
+				(r"\(\d\d:\d\d\)", "(HH:MM)"),
+				(r"\(\d\d:\d\d:\d\d\)", "(HH:MM:SS)"),
+				(r"── \d{4}-\d\d-\d\d ──", "── YYYY-MM-DD ──"),
+			]}, {
+				insta::assert_snapshot!($name, text(&terminal));
+			});
+		};
+	}
+
+	#[test]
+	fn auth_header_focused() {
+		assert_ui!("auth_header_focused", app(AppState::Auth, 0), 80, 24);
+	}
+
+	#[test]
+	fn auth_key_focused() {
+		let mut app = app(AppState::Auth, 1);
+		app.inputs[0] = "alice:wonder".to_string();
+		let mut terminal = render(&app, 80, 24);
+		assert_eq!(terminal.get_cursor().unwrap(), (14, 8));
+		assert_ui!("auth_key_focused", app, 80, 24);
+	}
+
+	#[test]
+	fn chat_untied() {
+		assert_ui!("chat_untied", app(AppState::Chat(Chat::default()), 1), 80, 20);
+	}
+
+	#[test]
+	fn chat_untied_header_focused() {
+		assert_ui!(
+			"chat_untied_header_focused",
+			app(AppState::Chat(Chat::default()), 0),
+			80,
+			20
+		);
+	}
+
+	#[test]
+	fn chat_tied() {
+		let mut app = app(tied(), 3);
+		app.subject_status = Some(Status {
+			presence: Presence::Away,
+			message: "lunch".to_string(),
+		});
+		app.inputs[2] = "See you".to_string();
+		app.chat_messages = vec![
+			message("alice", "Enjoy", MessageState::Delivered),
+			message("bob", "Brb, lunch", MessageState::Received),
+		];
+		assert_ui!("chat_tied", app, 80, 20);
+	}
+
+	#[test]
+	fn chat_message_altered() {
+		let mut app = app(tied(), 3);
+		app.chat_messages = vec![
+			message("bob", "\x1b[2Jgotcha", MessageState::Received),
+			message("bob", "pay\u{200B}pal", MessageState::Received),
+		];
+		assert_ui!("chat_message_altered", app, 80, 20);
+		app.show_invisible = true;
+		assert_ui!("chat_message_altered_invisible_shown", app, 80, 20);
+	}
+
+	#[test]
+	fn countdown_is_short() {
+		assert_eq!(countdown(Duration::from_secs(45)), "45s");
+		assert_eq!(countdown(Duration::from_secs(90)), "1m 30s");
+		assert_eq!(countdown(Duration::from_secs(300)), "5m");
+		assert_eq!(countdown(Duration::from_secs(7500)), "2h 5m");
+		assert_eq!(countdown(Duration::from_secs(86400)), "24h");
+	}
+
+	#[test]
+	fn ephemeral_message_counts_down() {
+		let mut app = app(tied(), 3);
+		let mut ephemeral = message("bob", "Burn after reading", MessageState::Received);
+		ephemeral.expires = Some(Utc::now() + chrono::Duration::hours(2));
+		app.chat_messages = vec![ephemeral, message("bob", "Hi", MessageState::Received)];
+		let text = text(&render(&app, 80, 20));
+		assert!(text.contains("Burn after reading [vanishes in 2h]") || text.contains("Burn after reading [vanishes in 1h 59m]"));
+		assert!(!text.contains("Hi ["));
+	}
+
+	#[test]
+	fn chat_days_are_separated() {
+		let mut app = app(tied(), 3);
+		let mut earlier = message("bob", "Yesterday's news", MessageState::Received);
+		earlier.time -= chrono::Duration::days(1);
+		let yesterday = format!("── {} ──", earlier.day().format("%Y-%m-%d"));
+		let today = message("bob", "Today's news", MessageState::Received);
+		let today_separator = format!("── {} ──", today.day().format("%Y-%m-%d"));
+		let stamp = today.time.with_timezone(&chrono::Local).format("(%d.%m %H:%M)").to_string();
+		app.chat_messages = vec![today, earlier];
+		app.config.time_format = "%d.%m %H:%M".to_string();
+		let text = text(&render(&app, 80, 20));
+		let yesterday = text.find(&yesterday).unwrap();
+		let today = text.find(&today_separator).unwrap();
+		assert!(yesterday < text.find("Yesterday's news").unwrap());
+		assert!(text.find("Yesterday's news").unwrap() < today);
+		assert!(today < text.find("Today's news").unwrap());
+		assert!(text.contains(&stamp));
+	}
+
+	#[test]
+	fn own_messages_are_distinguished() {
+		let mut app = app(tied(), 3);
+		app.theme = Theme::DEFAULT;
+		app.chat_messages = vec![
+			message("alice", "On my way", MessageState::Pending),
+			message("bob", "Where are you?", MessageState::Received),
+			message("alice", "Hi", MessageState::Delivered),
+		];
+		let terminal = render(&app, 80, 20);
+		let buffer = terminal.backend().buffer();
+		let text = text(&terminal);
+		// Cell where `needle` starts, the text having no wide characters before it
+		let at = |needle: &str| {
+			let (y, line) = text.lines().enumerate().find(|(_, line)| line.contains(needle)).unwrap();
+			buffer.get(line[..line.find(needle).unwrap()].chars().count() as u16, y as u16)
+		};
+		assert_eq!(at("bob:").fg, Theme::DEFAULT.nick("bob").fg.unwrap());
+		assert_eq!(at("alice: Hi").fg, Theme::DEFAULT.nick("alice").fg.unwrap());
+		assert_eq!(at("Hi").fg, Theme::DEFAULT.own.fg.unwrap());
+		assert_eq!(at("On my way").fg, Theme::DEFAULT.dim.fg.unwrap());
+		assert_eq!(at("Where are you?").fg, Theme::DEFAULT.messages.fg.unwrap());
+	}
+
+	#[test]
+	fn echo_delivers_pending_message() {
+		let mut app = app(tied(), 3);
+		app.chat_messages_add_pending("Hi");
+		app.chat_messages_add_pending("Hi");
+		assert_eq!(app.chat_messages.len(), 2);
+		assert!(app.chat_messages.iter().all(|m| m.state == MessageState::Pending && m.sender == "alice"));
+		let sent = Utc.with_ymd_and_hms(2022, 5, 1, 12, 30, 0).unwrap();
+		app.chat_messages_add("alice", "Hi", Some(sent), None);
+		assert_eq!(app.chat_messages.len(), 2);
+		assert_eq!(app.chat_messages[0].state, MessageState::Pending);
+		assert_eq!(app.chat_messages[1].state, MessageState::Delivered);
+		assert_eq!(app.chat_messages[1].time, sent);
+		// The subject may well send the same text
+		app.chat_messages_add("bob", "Hi", None, None);
+		assert_eq!(app.chat_messages.len(), 3);
+		assert_eq!(app.chat_messages[0].state, MessageState::Received);
+		assert_eq!(app.chat_messages[1].state, MessageState::Pending);
+		// Or claim the user's name, which doesn't make them the user
+		assert!(!app.chat_messages_add("alice", "Bye", None, None));
+		assert_eq!(app.chat_messages.len(), 4);
+		assert_eq!(app.chat_messages[0].state, MessageState::Received);
+		assert!(app.chat_messages_add("alice", "Hi", None, None));
+		assert_eq!(app.chat_messages[2].state, MessageState::Delivered);
+	}
+
+	#[test]
+	fn expired_messages_vanish() {
+		let mut app = app(tied(), 3);
+		let mut expired = message("bob", "Burn after reading", MessageState::Received);
+		expired.expires = Some(Utc::now() - chrono::Duration::seconds(1));
+		let mut ephemeral = message("bob", "Read on", MessageState::Received);
+		ephemeral.expires = Some(Utc::now() + chrono::Duration::minutes(1));
+		app.chat_messages = vec![
+			expired,
+			ephemeral,
+			message("bob", "Second", MessageState::Received),
+			message("bob", "First", MessageState::Received),
+		];
+		app.scroll = 2;
+		app.chat_messages_expire();
+		assert_eq!(app.chat_messages.len(), 3);
+		assert_eq!(app.chat_messages[app.scroll].text, "Second");
+	}
+
+	#[test]
+	fn ephemeral_messages_expire_after_arrival() {
+		let an_hour_ago = Utc::now() - chrono::Duration::hours(1);
+		let message = ChatMessage::received("bob", "Hi", Some(an_hour_ago), Some(Duration::from_secs(60)));
+		assert!(message.expires.unwrap() > Utc::now());
+		// Too long to count still isn't forever
+		let message = ChatMessage::received("bob", "Hi", Some(an_hour_ago), Some(Duration::MAX));
+		assert!(message.expires.is_some());
+		assert!(ChatMessage::received("bob", "Hi", Some(an_hour_ago), None).expires.is_none());
+	}
+
+	#[test]
+	fn chat_scrolled() {
+		let mut app = app(tied(), 3);
+		app.chat_messages = vec![
+			message("bob", "Third", MessageState::Received),
+			message("bob", "Second", MessageState::Received),
+			message("bob", "First", MessageState::Received),
+		];
+		app.scroll = 1;
+		let text = text(&render(&app, 80, 20));
+		assert!(text.contains("bob: Second") && !text.contains("bob: Third"));
+	}
+
+	#[test]
+	fn help_shown() {
+		let mut app = app(tied(), 3);
+		app.help_shown = true;
+		assert_ui!("help_shown", app, 80, 24);
+	}
+
+	#[test]
+	fn help_reflects_custom_keys() {
+		let mut custom = BTreeMap::new();
+		custom.insert(Action::Quit, vec![Key::try_from("ctrl+q".to_string()).unwrap()]);
+		let mut app = app(AppState::Auth, 0);
+		app.keymap = Keymap::new(&custom);
+		let text = text(&render(&app, 80, 24));
+		assert!(text.contains("[CTRL + Q] – Exit") && !text.contains("[F9]"));
+	}
+
+	#[test]
+	fn chat_tied_header_focused() {
+		assert_ui!("chat_tied_header_focused", app(tied(), 0), 80, 20);
+	}
+
+	#[test]
+	fn chat_status_editing() {
+		let mut app = app(tied(), 3);
+		app.status_editing = true;
+		app.inputs[2] = "Busy".to_string();
+		assert_ui!("chat_status_editing", app, 80, 20);
+	}
+
+	#[test]
+	fn chat_notice() {
+		let mut app = app(AppState::Chat(Chat::default()), 3);
+		app.chat_notice = Some(COMMAND_FAULT_UNTIED.to_string());
+		assert_ui!("chat_notice", app, 80, 20);
+	}
+
+	#[test]
+	fn chat_file_offered() {
+		let mut app = app(tied(), 3);
+		app.transfer = Some(files::Transfer::Offered(FileOffer {
+			name: "config.toml".to_string(),
+			size: 2048,
+			sha256: String::new(),
+		}));
+		assert_ui!("chat_file_offered", app, 80, 20);
+	}
+
+	#[test]
+	fn chat_file_name_is_sanitized() {
+		let mut app = app(tied(), 3);
+		app.transfer = Some(files::Transfer::Offered(FileOffer {
+			name: "\u{202E}txt.exe\x1b[2J".to_string(),
+			size: 2048,
+			sha256: String::new(),
+		}));
+		let text = text(&render(&app, 120, 20));
+		assert!(text.contains("\\u{202e}txt.exe\\u{1b}[2J"));
+	}
+
+	#[test]
+	fn chat_file_receiving() {
+		let mut download = Download::new(FileOffer {
+			name: "config.toml".to_string(),
+			size: 2048,
+			sha256: String::new(),
+		});
+		download.push(&[0; 1024]);
+		let mut app = app(tied(), 3);
+		app.transfer = Some(files::Transfer::Receiving(download));
+		assert_ui!("chat_file_receiving", app, 80, 20);
+	}
+
+	#[test]
+	fn chat_focus_is_highlighted() {
+		let app = app(tied(), 3);
+		let terminal = render(&app, 80, 20);
+		let buffer = terminal.backend().buffer();
+		// Message input is the last block, subject input is the second one
+		assert_eq!(buffer.get(0, 17).fg, Color::Cyan);
+		assert_eq!(buffer.get(0, 2).fg, Color::Reset);
+	}
+
+	#[test]
+	fn job_in_progress_abortable() {
+		let mut app = app(
+			job(JobState::InProgress(Some(JobSwitchAppState::Chat(Chat::default()))), 50),
+			0,
+		);
+		app.job_log = vec![
+			LogEntry::new(LogLevel::Info, "Tying with bob..."),
+			LogEntry::new(LogLevel::Info, &TIE_JOB_AWAITING),
+		];
+		assert_ui!("job_in_progress_abortable", app, 80, 20);
+	}
+
+	#[test]
+	fn job_log_is_colored() {
+		let mut app = app(job(JobState::Err(JobSwitchAppState::Auth), 50), 0);
+		app.job_log = vec![
+			LogEntry::new(LogLevel::Info, &TIE_JOB_AWAITING),
+			LogEntry::new(LogLevel::Fault, &TIE_JOB_FAULT_NOUSER),
+		];
+		let terminal = render(&app, 80, 20);
+		let buffer = terminal.backend().buffer();
+		// Messages start after the log block border and the timestamp
+		assert_eq!(buffer.get(28, 8).fg, Color::White);
+		assert_eq!(buffer.get(28, 9).fg, Color::LightRed);
+		assert_eq!(buffer.get(28, 9).bg, Color::Black);
+	}
+
+	#[test]
+	fn job_log_scrolled() {
+		let mut app = app(job(JobState::Err(JobSwitchAppState::Auth), 50), 0);
+		app.job_log = vec![
+			LogEntry::new(LogLevel::Info, &TIE_JOB_AWAITING),
+			LogEntry::new(LogLevel::Fault, &TIE_JOB_FAULT_NOUSER),
+		];
+		app.scroll = 1;
+		let text = text(&render(&app, 80, 20));
+		assert!(!text.contains(TIE_JOB_AWAITING.get()));
+		assert!(text.contains(TIE_JOB_FAULT_NOUSER.get()));
+	}
+
+	#[test]
+	fn chat_blocks_are_clicked() {
+		let area = Rect::new(0, 0, 80, 20);
+		let mut app = app(tied(), 0);
+		let (header, _) = chat_header(&app);
+		let hint = header.width() as u16;
+		assert_eq!(chat_target(&app, area, 0, 0), Some(ChatTarget::Header));
+		assert_eq!(chat_target(&app, area, hint, 0), Some(ChatTarget::HeaderHint));
+		assert_eq!(chat_target(&app, area, hint, 1), Some(ChatTarget::Header));
+		assert_eq!(chat_target(&app, area, 40, 3), Some(ChatTarget::Subject));
+		assert_eq!(chat_target(&app, area, 40, 6), Some(ChatTarget::EncryptionKey));
+		assert_eq!(chat_target(&app, area, 40, 10), Some(ChatTarget::Messages));
+		assert_eq!(chat_target(&app, area, 40, 18), Some(ChatTarget::NewMessage));
+		assert_eq!(chat_target(&app, area, 80, 18), None);
+		// The prompt is only there while the header is focused
+		app.input_focus = 3;
+		assert_eq!(chat_target(&app, area, hint, 0), Some(ChatTarget::Header));
+	}
+
+	#[test]
+	fn monochrome_has_no_colors() {
+		let mut job_app = app(job(JobState::Err(JobSwitchAppState::Auth), 50), 0);
+		job_app.theme = Theme::MONOCHROME;
+		job_app.job_log = vec![LogEntry::new(LogLevel::Fault, &TIE_JOB_FAULT_NOUSER)];
+		let terminal = render(&job_app, 80, 20);
+		let buffer = terminal.backend().buffer();
+		assert!(buffer.content.iter().all(|cell| cell.fg == Color::Reset && cell.bg == Color::Reset));
+		assert!(buffer.get(28, 8).modifier.contains(Modifier::BOLD));
+		let mut chat_app = app(tied(), 3);
+		chat_app.theme = Theme::MONOCHROME;
+		let terminal = render(&chat_app, 80, 20);
+		let buffer = terminal.backend().buffer();
+		assert!(buffer.content.iter().all(|cell| cell.fg == Color::Reset && cell.bg == Color::Reset));
+		assert!(buffer.get(0, 17).modifier.contains(Modifier::BOLD));
+	}
+
+	#[test]
+	fn job_in_progress() {
+		assert_ui!("job_in_progress", app(job(JobState::InProgress(None), 25), 0), 80, 20);
+	}
+
+	#[test]
+	fn job_ok() {
+		let mut app = app(
+			job(JobState::Ok(JobSwitchAppState::Chat(Chat::default())), 100),
+			0,
+		);
+		app.job_log = vec![LogEntry::new(LogLevel::Success, &JOB_SUCCESS)];
+		assert_ui!("job_ok", app, 80, 20);
+	}
+
+	#[test]
+	fn job_err() {
+		let mut app = app(job(JobState::Err(JobSwitchAppState::Auth), 50), 0);
+		app.job_log = vec![LogEntry::new(LogLevel::Fault, &TIE_JOB_FAULT_NOUSER)];
+		assert_ui!("job_err", app, 80, 20);
+	}
+
+	#[test]
+	fn narrow_auth() {
+		assert_ui!("narrow_auth", app(AppState::Auth, 1), 24, 20);
+	}
+
+	#[test]
+	fn narrow_chat() {
+		let mut app = app(tied(), 3);
+		app.chat_messages = vec![message("bob", "A rather long message", MessageState::Received)];
+		assert_ui!("narrow_chat", app, 24, 16);
+	}
+
+	#[test]
+	fn narrow_job() {
+		let mut app = app(job(JobState::Err(JobSwitchAppState::Auth), 50), 0);
+		app.job_log = vec![LogEntry::new(LogLevel::Fault, &TIE_JOB_FAULT_NOUSER)];
+		assert_ui!("narrow_job", app, 40, 12);
+	}
+}
@@ -0,0 +1,268 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+mod support;
+
+use husky::{Client, Error, Event, Events, Presence, Status, UserKey};
+use std::time::Duration;
+use support::{MockServer, ACCESS_KEY};
+
+const ALICE: &str = "alice:wonderland";
+const BOB: &str = "bob:builder";
+
+async fn next(events: &mut Events) -> Event {
+	tokio::time::timeout(Duration::from_secs(5), events.next())
+		.await
+		.expect("no event in time")
+		.expect("connection closed")
+		.expect("socket error")
+}
+
+async fn closed(events: &mut Events) {
+	while let Some(event) = tokio::time::timeout(Duration::from_secs(5), events.next())
+		.await
+		.expect("connection not closed in time")
+	{
+		event.ok();
+	}
+}
+
+async fn login(server: &MockServer, key: &str) -> (Client, Events) {
+	let (mut client, mut events) = Client::connect(&server.endpoint()).await.unwrap();
+	client.auth(ACCESS_KEY, &UserKey::new(key.to_string())).unwrap();
+	assert_eq!(next(&mut events).await, Event::AuthOk);
+	(client, events)
+}
+
+/// Alice ties first, Bob ties back
+async fn tied(server: &MockServer) -> ((Client, Events), (Client, Events)) {
+	let (alice, mut alice_events) = login(server, ALICE).await;
+	let (bob, mut bob_events) = login(server, BOB).await;
+	alice.tie("bob").unwrap();
+	assert_eq!(next(&mut alice_events).await, Event::TieWait);
+	bob.tie("alice").unwrap();
+	assert_eq!(next(&mut alice_events).await, Event::TieOk);
+	assert_eq!(next(&mut bob_events).await, Event::TieOk);
+	((alice, alice_events), (bob, bob_events))
+}
+
+#[tokio::test]
+async fn preconnect_approval() {
+	let server = MockServer::start(&[ALICE]).await;
+	assert_eq!(Client::preconnect(&server.endpoint()).await, Ok(()));
+	server.set_approving(false);
+	assert_eq!(
+		Client::preconnect(&server.endpoint()).await,
+		Err(Error::PreconnectDisapproved)
+	);
+}
+
+#[tokio::test]
+async fn auth_faults() {
+	let server = MockServer::start(&[ALICE]).await;
+	let (mut client, mut events) = Client::connect(&server.endpoint()).await.unwrap();
+	client.auth("wrong key", &UserKey::new(ALICE.to_string())).unwrap();
+	assert_eq!(next(&mut events).await, Event::AuthFault);
+	client.auth(ACCESS_KEY, &UserKey::new("alice:wrong".to_string())).unwrap();
+	assert_eq!(next(&mut events).await, Event::AuthFault);
+	let _alice = login(&server, ALICE).await;
+	client.auth(ACCESS_KEY, &UserKey::new(ALICE.to_string())).unwrap();
+	assert_eq!(next(&mut events).await, Event::AuthFaultOverauth);
+}
+
+#[tokio::test]
+async fn tie_faults() {
+	let server = MockServer::start(&[ALICE, BOB]).await;
+	let ((alice, mut alice_events), _bob) = tied(&server).await;
+	alice.tie("alice").unwrap();
+	assert_eq!(next(&mut alice_events).await, Event::TieFaultSelfTie);
+	alice.tie("bob").unwrap();
+	assert_eq!(next(&mut alice_events).await, Event::TieFaultOverTie);
+	alice.untie().unwrap();
+	assert_eq!(next(&mut alice_events).await, Event::Ok);
+	alice.tie("carol").unwrap();
+	assert_eq!(next(&mut alice_events).await, Event::TieFaultNoUser);
+}
+
+#[tokio::test]
+async fn messages_are_relayed_and_echoed() {
+	let server = MockServer::start(&[ALICE, BOB]).await;
+	let ((alice, mut alice_events), (bob, mut bob_events)) = tied(&server).await;
+	alice.send("Hi: Bob").unwrap();
+	let hi = next(&mut bob_events).await;
+	assert!(matches!(&hi, Event::Message { sender, text, time: Some(_), ttl: None } if sender == "alice" && text == "Hi: Bob"));
+	assert_eq!(next(&mut alice_events).await, hi);
+	bob.send("Hello").unwrap();
+	let hello = next(&mut alice_events).await;
+	assert!(matches!(&hello, Event::Message { sender, text, time: Some(_), ttl: None } if sender == "bob" && text == "Hello"));
+	assert_eq!(next(&mut bob_events).await, hello);
+}
+
+#[tokio::test]
+async fn ephemeral_message_keeps_ttl() {
+	let server = MockServer::start(&[ALICE, BOB]).await;
+	let ((alice, mut alice_events), (_bob, mut bob_events)) = tied(&server).await;
+	alice.send_ephemeral("Burn after reading", Duration::from_secs(30)).unwrap();
+	let burn = next(&mut bob_events).await;
+	assert!(matches!(&burn, Event::Message { text, ttl: Some(ttl), .. } if text == "Burn after reading" && ttl.as_secs() == 30));
+	assert_eq!(next(&mut alice_events).await, burn);
+}
+
+#[tokio::test]
+async fn message_without_tie_faults() {
+	let server = MockServer::start(&[ALICE]).await;
+	let (alice, mut events) = login(&server, ALICE).await;
+	alice.send("Anyone?").unwrap();
+	assert_eq!(next(&mut events).await, Event::Fault);
+}
+
+#[tokio::test]
+async fn status_is_relayed() {
+	let server = MockServer::start(&[ALICE, BOB]).await;
+	let ((alice, mut alice_events), (_bob, mut bob_events)) = tied(&server).await;
+	let status = Status {
+		presence: Presence::Away,
+		message: "brb".to_string(),
+	};
+	alice.set_status(&status).unwrap();
+	assert_eq!(next(&mut bob_events).await, Event::Status(status));
+	assert_eq!(next(&mut alice_events).await, Event::Ok);
+}
+
+#[tokio::test]
+async fn untie_notifies_subject() {
+	let server = MockServer::start(&[ALICE, BOB]).await;
+	let ((_alice, mut alice_events), (bob, mut bob_events)) = tied(&server).await;
+	bob.untie().unwrap();
+	assert_eq!(next(&mut alice_events).await, Event::Untie);
+	assert_eq!(next(&mut bob_events).await, Event::Ok);
+	assert!(server.ties().is_empty());
+}
+
+#[tokio::test]
+async fn untie_while_waiting_leaves_waitlist() {
+	let server = MockServer::start(&[ALICE, BOB]).await;
+	let (alice, mut alice_events) = login(&server, ALICE).await;
+	let (bob, mut bob_events) = login(&server, BOB).await;
+	alice.tie("bob").unwrap();
+	assert_eq!(next(&mut alice_events).await, Event::TieWait);
+	alice.untie().unwrap();
+	assert_eq!(next(&mut alice_events).await, Event::Ok);
+	bob.tie("alice").unwrap();
+	assert_eq!(next(&mut bob_events).await, Event::TieWait);
+}
+
+#[tokio::test]
+async fn drop_me_frees_user_and_unties() {
+	let server = MockServer::start(&[ALICE, BOB]).await;
+	let ((alice, mut alice_events), (_bob, mut bob_events)) = tied(&server).await;
+	alice.drop_me().unwrap();
+	closed(&mut alice_events).await;
+	assert_eq!(next(&mut bob_events).await, Event::Untie);
+	assert_eq!(server.approved(), vec!["bob".to_string()]);
+	// Same user may log in again right away
+	login(&server, ALICE).await;
+	assert_eq!(server.received().last(), Some(&format!("A{}/{}", ACCESS_KEY, ALICE)));
+}
+
+#[tokio::test]
+async fn close_waits_for_socket_to_close() {
+	let server = MockServer::start(&[ALICE, BOB]).await;
+	let ((alice, _alice_events), (_bob, mut bob_events)) = tied(&server).await;
+	alice.close().await;
+	assert_eq!(alice.send("Still there?"), Err(husky::Error::Disconnected));
+	assert_eq!(next(&mut bob_events).await, Event::Untie);
+	assert_eq!(server.received().last(), Some(&"X".to_string()));
+	// Closing twice is fine
+	alice.close().await;
+}
+
+#[tokio::test]
+async fn dropped_connection_unties() {
+	let server = MockServer::start(&[ALICE, BOB]).await;
+	let ((alice, alice_events), (_bob, mut bob_events)) = tied(&server).await;
+	drop(alice);
+	drop(alice_events);
+	assert_eq!(next(&mut bob_events).await, Event::Untie);
+}
+
+#[tokio::test]
+async fn file_is_transferred_chunk_by_chunk() {
+	let server = MockServer::start(&[ALICE, BOB]).await;
+	let ((alice, mut alice_events), (bob, mut bob_events)) = tied(&server).await;
+	let dir = std::env::temp_dir().join(format!("husky-client-test-{}", std::process::id()));
+	std::fs::create_dir_all(&dir).unwrap();
+	let data: Vec<u8> = (0..husky::CHUNK_SIZE * 3 / 2).map(|i| (i % 251) as u8).collect();
+	std::fs::write(dir.join("config.toml"), &data).unwrap();
+	let mut upload = husky::Upload::open(&dir.join("config.toml")).unwrap();
+	alice.offer_file(upload.offer()).unwrap();
+	assert_eq!(next(&mut alice_events).await, Event::Ok);
+	let offer = match next(&mut bob_events).await {
+		Event::FileOffer(offer) => offer,
+		other => panic!("unexpected {:?}", other),
+	};
+	assert_eq!(&offer, upload.offer());
+	let mut download = husky::Download::new(offer);
+	bob.accept_file().unwrap();
+	assert_eq!(next(&mut bob_events).await, Event::Ok);
+	assert_eq!(next(&mut alice_events).await, Event::FileAccept);
+	loop {
+		alice.send_file_chunk(upload.next_chunk().unwrap()).unwrap();
+		assert_eq!(next(&mut alice_events).await, Event::Ok);
+		let chunk = match next(&mut bob_events).await {
+			Event::FileChunk(chunk) => chunk,
+			other => panic!("unexpected {:?}", other),
+		};
+		if download.push(&chunk) {
+			bob.file_done(download.verify()).unwrap();
+			break;
+		}
+		bob.ack_file_chunk().unwrap();
+		assert_eq!(next(&mut bob_events).await, Event::Ok);
+		assert_eq!(next(&mut alice_events).await, Event::FileAck);
+	}
+	assert_eq!(next(&mut alice_events).await, Event::FileDone { verified: true });
+	std::fs::remove_dir_all(&dir).ok();
+}
+
+#[tokio::test]
+async fn file_frames_without_tie_fault() {
+	let server = MockServer::start(&[ALICE]).await;
+	let (alice, mut events) = login(&server, ALICE).await;
+	alice.decline_file().unwrap();
+	assert_eq!(next(&mut events).await, Event::Fault);
+}
+
+#[tokio::test]
+async fn trace_records_frames_with_secrets_redacted() {
+	let server = MockServer::start(&[ALICE]).await;
+	let path = std::env::temp_dir().join(format!("husky-trace-test-{}.jsonl", std::process::id()));
+	let trace = husky::Trace::create(&path).unwrap();
+	let (mut client, mut events) = Client::connect_traced(&server.endpoint(), trace).await.unwrap();
+	client.auth(ACCESS_KEY, &UserKey::new(ALICE.to_string())).unwrap();
+	assert_eq!(next(&mut events).await, Event::AuthOk);
+	client.drop_me().unwrap();
+	closed(&mut events).await;
+	let records: Vec<husky::TraceRecord> = std::fs::read_to_string(&path)
+		.unwrap()
+		.lines()
+		.map(|line| serde_json::from_str(line).unwrap())
+		.collect();
+	let frames: Vec<(husky::Direction, &str)> = records
+		.iter()
+		.map(|r| (r.direction, r.frame.as_str()))
+		.collect();
+	assert_eq!(
+		frames,
+		vec![
+			(husky::Direction::Tx, "A[REDACTED]/alice:[REDACTED]"),
+			(husky::Direction::Rx, "O"),
+			(husky::Direction::Tx, "X"),
+		]
+	);
+	assert!(records.windows(2).all(|w| w[0].time <= w[1].time));
+	std::fs::remove_file(&path).ok();
+}
@@ -0,0 +1,343 @@
+/***************************
+COPYRIGHT LESTER COVEY (me@lestercovey.ml),
+2022
+
+***************************/
+
+//! In-process stand-in for `server.php` and `preconnect.php`, so client flows can be tested offline.
+//! Flag semantics (approved map, waitlist, ties) follow `server.php` as closely as possible.
+
+#![allow(dead_code)]
+
+use futures_util::{SinkExt, StreamExt};
+use husky::flags::*;
+use husky::Endpoint;
+use std::{
+	collections::HashMap,
+	sync::{
+		atomic::{AtomicBool, Ordering},
+		Arc, Mutex,
+	},
+};
+use tokio::{
+	io::{AsyncReadExt, AsyncWriteExt},
+	net::TcpListener,
+	sync::mpsc,
+};
+use tokio_tungstenite::{accept_async, tungstenite::Message};
+
+pub const ACCESS_KEY: &str = "test-access-key";
+
+/// Connection id, stands for PHP's stream resource
+type Conn = usize;
+
+enum Outgoing {
+	Frame(String),
+	Close,
+}
+
+#[derive(Default)]
+struct State {
+	user_keys: Vec<String>,
+	user_names: Vec<String>,
+	connections: HashMap<Conn, mpsc::UnboundedSender<Outgoing>>,
+	approved: HashMap<String, Conn>,
+	waitlist: HashMap<String, String>,
+	ties: HashMap<String, String>,
+	/// Every frame received, in order, for assertions
+	log: Vec<String>,
+}
+
+impl State {
+	fn write(&self, conn: Conn, frame: &str) {
+		if let Some(tx) = self.connections.get(&conn) {
+			tx.send(Outgoing::Frame(frame.to_string())).ok();
+		}
+	}
+	fn write_to(&self, user_name: &str, frame: &str) {
+		if let Some(conn) = self.approved.get(user_name) {
+			self.write(*conn, frame);
+		}
+	}
+	fn user_of(&self, conn: Conn) -> Option<String> {
+		self.approved
+			.iter()
+			.find(|(_, c)| **c == conn)
+			.map(|(name, _)| name.clone())
+	}
+	fn tie_peer(&self, user_name: &str) -> Option<String> {
+		if let Some(peer) = self.ties.get(user_name) {
+			return Some(peer.clone());
+		}
+		self.ties
+			.iter()
+			.find(|(_, v)| *v == user_name)
+			.map(|(k, _)| k.clone())
+	}
+	fn untie(&mut self, user_name: &str) {
+		if let Some(peer) = self.ties.remove(user_name) {
+			self.write_to(&peer, &RXTX_UNTIE_FLAG.to_string());
+		} else if let Some(peer) = self.tie_peer(user_name) {
+			self.write_to(&peer, &RXTX_UNTIE_FLAG.to_string());
+			self.ties.remove(&peer);
+		} else {
+			self.waitlist.remove(user_name);
+		}
+	}
+	fn conn_close(&mut self, conn: Conn) {
+		if let Some(tx) = self.connections.remove(&conn) {
+			tx.send(Outgoing::Close).ok();
+		}
+		if let Some(user_name) = self.user_of(conn) {
+			self.approved.remove(&user_name);
+			self.waitlist.remove(&user_name);
+			self.untie(&user_name);
+		}
+	}
+	/// Mirrors `on_message` of `server.php`
+	fn on_message(&mut self, conn: Conn, txt: &str) {
+		self.log.push(txt.to_string());
+		let mut chars = txt.chars();
+		let flag = match chars.next() {
+			Some(flag) => flag,
+			None => return,
+		};
+		let body = chars.as_str();
+		let mut response = RXTX_OK_FLAG.to_string();
+		match flag {
+			TX_AUTH_FLAG => {
+				let (access_key, user_key) = body.split_once('/').unwrap_or((body, ""));
+				let user_name = user_key.split(':').next().unwrap_or_default().to_string();
+				response = if self.approved.contains_key(&user_name) {
+					RX_AUTH_FAULT_OVERAUTH_FLAG
+				} else if access_key == ACCESS_KEY && self.user_keys.iter().any(|k| k == user_key) {
+					self.approved.insert(user_name, conn);
+					RX_AUTH_OK_FLAG
+				} else {
+					RX_AUTH_FAULT_FLAG
+				}
+				.to_string();
+			}
+			TX_DROPME_FLAG => {
+				self.conn_close(conn);
+				return;
+			}
+			TX_TIE_INIT_FLAG => {
+				let user_name = match self.user_of(conn) {
+					Some(user_name) => user_name,
+					None => {
+						self.conn_close(conn);
+						return;
+					}
+				};
+				response = if user_name == body {
+					RX_TIE_FAULT_SELFTIE_FLAG
+				} else if self.tie_peer(&user_name).is_some() {
+					RX_TIE_FAULT_OVERTIE_FLAG
+				} else if self.user_names.iter().any(|n| n == body) {
+					if self.waitlist.get(body) == Some(&user_name) {
+						self.ties.insert(body.to_string(), user_name);
+						self.waitlist.remove(body);
+						self.write_to(body, &RX_TIE_OK_FLAG.to_string());
+						RX_TIE_OK_FLAG
+					} else {
+						self.waitlist.insert(user_name, body.to_string());
+						RX_TIE_OK_WAIT_FLAG
+					}
+				} else {
+					RX_TIE_FAULT_NOUSER_FLAG
+				}
+				.to_string();
+			}
+			RXTX_UNTIE_FLAG => match self.user_of(conn) {
+				Some(user_name) => self.untie(&user_name),
+				None => {
+					self.conn_close(conn);
+					return;
+				}
+			},
+			RXTX_MESSAGE_FLAG => match self.user_of(conn) {
+				Some(user_name) => match self.tie_peer(&user_name) {
+					Some(peer) => {
+						self.write_to(&peer, &format!("{}{}", RXTX_MESSAGE_FLAG, body));
+						response = txt.to_string();
+					}
+					None => response = RXTX_FAULT_FLAG.to_string(),
+				},
+				None => {
+					self.conn_close(conn);
+					return;
+				}
+			},
+			RXTX_STATUS_FLAG => match self.user_of(conn) {
+				Some(user_name) => {
+					if let Some(peer) = self.tie_peer(&user_name) {
+						self.write_to(&peer, txt);
+					}
+				}
+				None => {
+					self.conn_close(conn);
+					return;
+				}
+			},
+			RXTX_FILE_OFFER_FLAG
+			| RXTX_FILE_ACCEPT_FLAG
+			| RXTX_FILE_DECLINE_FLAG
+			| RXTX_FILE_CHUNK_FLAG
+			| RXTX_FILE_ACK_FLAG
+			| RXTX_FILE_DONE_FLAG => match self.user_of(conn) {
+				Some(user_name) => match self.tie_peer(&user_name) {
+					Some(peer) => self.write_to(&peer, txt),
+					None => response = RXTX_FAULT_FLAG.to_string(),
+				},
+				None => {
+					self.conn_close(conn);
+					return;
+				}
+			},
+			_ => (),
+		}
+		self.write(conn, &response);
+	}
+}
+
+/// Running mock server. Everything is torn down when the test's runtime stops
+pub struct MockServer {
+	state: Arc<Mutex<State>>,
+	approving: Arc<AtomicBool>,
+	http_port: u16,
+	ws_port: u16,
+}
+
+impl MockServer {
+	/// Start server which knows about given `username:password` keys
+	pub async fn start(user_keys: &[&str]) -> MockServer {
+		let state = Arc::new(Mutex::new(State {
+			user_keys: user_keys.iter().map(|k| k.to_string()).collect(),
+			user_names: user_keys
+				.iter()
+				.map(|k| k.split(':').next().unwrap().to_string())
+				.collect(),
+			..State::default()
+		}));
+		let approving = Arc::new(AtomicBool::new(true));
+		let http = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let ws = TcpListener::bind("127.0.0.1:0").await.unwrap();
+		let http_port = http.local_addr().unwrap().port();
+		let ws_port = ws.local_addr().unwrap().port();
+		let http_approving = approving.clone();
+		tokio::spawn(async move {
+			while let Ok((mut stream, _)) = http.accept().await {
+				let approving = http_approving.load(Ordering::SeqCst);
+				tokio::spawn(async move {
+					let mut request = Vec::new();
+					let mut buf = [0u8; 1024];
+					while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+						match stream.read(&mut buf).await {
+							Ok(0) | Err(_) => return,
+							Ok(n) => request.extend_from_slice(&buf[..n]),
+						}
+					}
+					let found = request.starts_with(b"GET /preconnect.php ");
+					let (status, body) = match (found, approving) {
+						(false, _) => ("404 Not Found", ""),
+						(true, true) => ("200 OK", "Ok"),
+						(true, false) => ("200 OK", "Err"),
+					};
+					let response = format!(
+						"HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+						status,
+						body.len(),
+						body
+					);
+					stream.write_all(response.as_bytes()).await.ok();
+				});
+			}
+		});
+		let ws_state = state.clone();
+		tokio::spawn(async move {
+			let mut next_conn: Conn = 0;
+			while let Ok((stream, _)) = ws.accept().await {
+				let conn = next_conn;
+				next_conn += 1;
+				let state = ws_state.clone();
+				tokio::spawn(async move {
+					let socket = match accept_async(stream).await {
+						Ok(ok) => ok,
+						Err(_) => return,
+					};
+					let (mut write, mut read) = socket.split();
+					let (tx, mut rx) = mpsc::unbounded_channel();
+					state.lock().unwrap().connections.insert(conn, tx);
+					tokio::spawn(async move {
+						while let Some(outgoing) = rx.recv().await {
+							match outgoing {
+								Outgoing::Frame(frame) => {
+									if write.send(Message::Text(frame)).await.is_err() {
+										break;
+									}
+								}
+								Outgoing::Close => {
+									write.close().await.ok();
+									break;
+								}
+							}
+						}
+					});
+					while let Some(Ok(message)) = read.next().await {
+						match message {
+							Message::Text(txt) => {
+								let mut state = state.lock().unwrap();
+								state.on_message(conn, &txt);
+								if !state.connections.contains_key(&conn) {
+									return;
+								}
+							}
+							// Client is gone as soon as it says so, not once the closing handshake is done
+							Message::Close(_) => break,
+							_ => (),
+						}
+					}
+					state.lock().unwrap().conn_close(conn);
+				});
+			}
+		});
+		MockServer {
+			state,
+			approving,
+			http_port,
+			ws_port,
+		}
+	}
+	/// Endpoint for the client to connect to
+	pub fn endpoint(&self) -> Endpoint {
+		Endpoint::new(
+			&format!("127.0.0.1:{}", self.http_port),
+			&self.ws_port.to_string(),
+		)
+	}
+	/// Make `preconnect.php` approve (or disapprove) connections
+	pub fn set_approving(&self, approving: bool) {
+		self.approving.store(approving, Ordering::SeqCst);
+	}
+	/// Every frame the server received so far
+	pub fn received(&self) -> Vec<String> {
+		self.state.lock().unwrap().log.clone()
+	}
+	/// Names of currently authorized users
+	pub fn approved(&self) -> Vec<String> {
+		let mut names: Vec<String> = self.state.lock().unwrap().approved.keys().cloned().collect();
+		names.sort();
+		names
+	}
+	/// Current ties as `(initiator, subject)` pairs
+	pub fn ties(&self) -> Vec<(String, String)> {
+		self.state
+			.lock()
+			.unwrap()
+			.ties
+			.iter()
+			.map(|(k, v)| (k.clone(), v.clone()))
+			.collect()
+	}
+}